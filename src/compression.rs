@@ -0,0 +1,596 @@
+//! Compression detection and codecs for tar archives
+//!
+//! Archives are recognised by their content: the magic bytes of the
+//! compression format, or the ustar magic of a plain tar header. File name
+//! suffixes are only used to choose the codec of a new archive.
+
+use flate2::read::MultiGzDecoder;
+use flate2::GzBuilder;
+use flate2::write::GzEncoder;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// Compression format of an archive file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// Plain tar
+    None,
+    /// gzip (`.tar.gz`, `.tgz`)
+    Gzip,
+    /// bzip2 (`.tar.bz2`, `.tbz2`)
+    Bzip2,
+    /// xz (`.tar.xz`, `.txz`)
+    Xz,
+    /// Zstandard (`.tar.zst`, `.tzst`)
+    Zstd,
+    /// LZ4 frame format (`.tar.lz4`)
+    Lz4,
+    /// Unix compress, LZW (`.tar.Z`, `.taz`)
+    Compress,
+}
+
+/// Offset of the ustar magic in a tar header
+const USTAR_MAGIC_OFFSET: usize = 257;
+
+impl Compression {
+    /// Detect the compression format from the first bytes of a file
+    ///
+    /// A plain tar header with the ustar magic at offset 257 is recognised
+    /// first, so an archive whose first entry name happens to start with a
+    /// compression magic is not misdetected. Unknown data is reported as
+    /// `None` and left to the tar parser.
+    pub fn detect(data: &[u8]) -> Self {
+        if is_ustar(data) {
+            return Compression::None;
+        }
+        match data {
+            [0x1f, 0x8b, ..] => Compression::Gzip,
+            [b'B', b'Z', b'h', b'1'..=b'9', ..] => Compression::Bzip2,
+            [0xfd, b'7', b'z', b'X', b'Z', 0x00, ..] => Compression::Xz,
+            // a regular frame, or a skippable frame (seekable archives)
+            [0x28, 0xb5, 0x2f, 0xfd, ..] | [0x50..=0x5f, 0x2a, 0x4d, 0x18, ..] => Compression::Zstd,
+            [0x04, 0x22, 0x4d, 0x18, ..] => Compression::Lz4,
+            [0x1f, 0x9d, ..] => Compression::Compress,
+            _ => Compression::None,
+        }
+    }
+
+    /// Detect the compression format of a file from its first bytes
+    pub fn detect_file<P: AsRef<Path>>(path: P) -> Result<Self, io::Error> {
+        let mut head = Vec::with_capacity(512);
+        std::fs::File::open(path)?.take(512).read_to_end(&mut head)?;
+        Ok(Self::detect(&head))
+    }
+
+    /// Choose the compression format from a file name suffix
+    /// Used to pick the codec when writing a new archive
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
+        let name = path.as_ref().to_string_lossy().to_lowercase();
+        let ends = |suffixes: &[&str]| suffixes.iter().any(|s| name.ends_with(s));
+        if ends(&[".gz", ".tgz"]) {
+            Compression::Gzip
+        } else if ends(&[".bz2", ".tbz2", ".tbz"]) {
+            Compression::Bzip2
+        } else if ends(&[".xz", ".txz"]) {
+            Compression::Xz
+        } else if ends(&[".zst", ".tzst"]) {
+            Compression::Zstd
+        } else if ends(&[".lz4"]) {
+            Compression::Lz4
+        } else if ends(&[".z", ".taz"]) {
+            Compression::Compress
+        } else {
+            Compression::None
+        }
+    }
+
+    /// Name of the format, as used in error messages
+    pub fn name(&self) -> &'static str {
+        match self {
+            Compression::None => "tar",
+            Compression::Gzip => "gzip",
+            Compression::Bzip2 => "bzip2",
+            Compression::Xz => "xz",
+            Compression::Zstd => "zstd",
+            Compression::Lz4 => "lz4",
+            Compression::Compress => "compress",
+        }
+    }
+}
+
+/// Returns true if `data` starts with a tar header carrying the ustar magic
+/// (POSIX "ustar\0" or GNU "ustar  ")
+pub fn is_ustar(data: &[u8]) -> bool {
+    data.get(USTAR_MAGIC_OFFSET..USTAR_MAGIC_OFFSET + 5) == Some(b"ustar")
+}
+
+/// Settings for writing compressed archives
+#[derive(Debug, Clone, Default)]
+pub struct CompressOptions {
+    /// Compression level; the codec default if None
+    /// (gzip: 0 to 9, bzip2: 1 to 9, xz: 0 to 9, zstd: 1 to 22 and
+    /// negative values for the fast modes)
+    pub level: Option<i32>,
+    /// gzip specific settings
+    pub gzip: GzipOptions,
+    /// Zstandard specific settings
+    pub zstd: ZstdOptions,
+}
+
+/// gzip settings
+#[derive(Debug, Clone, Default)]
+pub struct GzipOptions {
+    /// Number of worker threads; with more than one, 1 MiB blocks are
+    /// compressed in parallel as separate gzip members (as pigz does)
+    pub threads: usize,
+    /// Write one member per entry plus an index for random access
+    /// (see `IndexedGzip`); compresses on a single thread
+    pub indexed: bool,
+    /// Header fields of the gzip stream
+    pub header: GzipHeader,
+}
+
+/// gzip header fields (RFC 1952)
+#[derive(Debug, Clone, Default)]
+pub struct GzipHeader {
+    /// Modification time in Unix seconds; zero if None, so identical
+    /// inputs give identical bytes
+    pub mtime: Option<u32>,
+    /// Operating system byte, e.g. 3 for Unix; 255 (unknown) if None
+    pub operating_system: Option<u8>,
+    /// Original file name (FNAME)
+    pub filename: Option<String>,
+    /// Comment (FCOMMENT)
+    pub comment: Option<String>,
+}
+
+impl GzipOptions {
+    /// The header to write, with the defaults filled in and the text fields checked
+    pub(crate) fn resolved_header(&self) -> Result<GzipHeader, io::Error> {
+        for (field, value) in [("file name", &self.header.filename), ("comment", &self.header.comment)] {
+            if value.as_ref().is_some_and(|v| v.contains('\0')) {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("gzip {} contains a NUL byte", field)));
+            }
+        }
+        Ok(GzipHeader {
+            mtime: Some(self.header.mtime.unwrap_or(0)),
+            operating_system: Some(self.header.operating_system.unwrap_or(255)),
+            ..self.header.clone()
+        })
+    }
+}
+
+impl GzipHeader {
+    /// Builder writing this header
+    /// Only the first member of a multi-member stream carries the name and comment
+    pub(crate) fn builder(&self, first: bool) -> GzBuilder {
+        let mut builder = GzBuilder::new()
+            .mtime(self.mtime.unwrap_or(0))
+            .operating_system(self.operating_system.unwrap_or(255));
+        if first {
+            if let Some(filename) = &self.filename {
+                builder = builder.filename(filename.as_bytes());
+            }
+            if let Some(comment) = &self.comment {
+                builder = builder.comment(comment.as_bytes());
+            }
+        }
+        builder
+    }
+}
+
+/// Zstandard settings (used with the `zstd` feature)
+#[derive(Debug, Clone, Default)]
+pub struct ZstdOptions {
+    /// Enable long-distance matching with a 128 MiB window (`zstd --long`)
+    pub long_distance: bool,
+    /// Trained dictionary (`zstd --train`); also needed to read the archive
+    pub dictionary: Option<Vec<u8>>,
+    /// Write the seekable format: one frame per entry plus a seek table
+    /// (see `SeekableZstd`)
+    pub seekable: bool,
+}
+
+/// Window size used for long-distance matching, as `zstd --long`
+#[cfg(feature = "zstd")]
+const ZSTD_LONG_WINDOW_LOG: u32 = 27;
+/// Largest window accepted when reading, so `zstd --long=31` archives open
+#[cfg(feature = "zstd")]
+const ZSTD_MAX_WINDOW_LOG: u32 = 31;
+
+fn unsupported(compression: Compression) -> io::Error {
+    let hint = match compression {
+        Compression::Bzip2 => "; build tar_light with the `bzip2` feature",
+        Compression::Xz => "; build tar_light with the `xz` feature",
+        Compression::Zstd => "; build tar_light with the `zstd` feature",
+        Compression::Compress => " for writing; it can only be read",
+        _ => "",
+    };
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("{} compression is not supported{}", compression.name(), hint),
+    )
+}
+
+/// The level of the options, or `default`, checked against the codec's range
+fn checked_level(
+    options: &CompressOptions,
+    compression: Compression,
+    range: std::ops::RangeInclusive<i32>,
+    default: i32,
+) -> Result<i32, io::Error> {
+    let level = options.level.unwrap_or(default);
+    if !range.contains(&level) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid {} level: {}", compression.name(), level),
+        ));
+    }
+    Ok(level)
+}
+
+/// Create a zstd frame encoder from the options
+#[cfg(feature = "zstd")]
+pub(crate) fn zstd_encoder<W: Write>(
+    writer: W,
+    options: &CompressOptions,
+) -> Result<zstd::stream::write::Encoder<'static, W>, io::Error> {
+    let level = checked_level(options, Compression::Zstd, zstd::compression_level_range(), zstd::DEFAULT_COMPRESSION_LEVEL)?;
+    let mut encoder = match &options.zstd.dictionary {
+        Some(dictionary) => zstd::stream::write::Encoder::with_dictionary(writer, level, dictionary)?,
+        None => zstd::stream::write::Encoder::new(writer, level)?,
+    };
+    encoder.include_checksum(true)?;
+    if options.zstd.long_distance {
+        encoder.long_distance_matching(true)?;
+        encoder.window_log(ZSTD_LONG_WINDOW_LOG)?;
+    }
+    Ok(encoder)
+}
+
+/// Create a zstd decoder, using the dictionary of the options if any
+#[cfg(feature = "zstd")]
+pub(crate) fn zstd_decoder<R: Read>(
+    reader: R,
+    options: &CompressOptions,
+) -> Result<zstd::stream::read::Decoder<'static, io::BufReader<R>>, io::Error> {
+    let mut decoder = match &options.zstd.dictionary {
+        Some(dictionary) => zstd::stream::read::Decoder::with_dictionary(io::BufReader::new(reader), dictionary)?,
+        None => zstd::stream::read::Decoder::new(reader)?,
+    };
+    decoder.window_log_max(ZSTD_MAX_WINDOW_LOG)?;
+    Ok(decoder)
+}
+
+/// Wrap `reader` in a streaming decoder for the given format, using the
+/// dictionary of `options`
+#[cfg_attr(not(feature = "zstd"), allow(unused_variables))]
+pub(crate) fn decoder<'a, R: Read + 'a>(
+    reader: R,
+    compression: Compression,
+    options: &CompressOptions,
+) -> Result<Box<dyn Read + 'a>, io::Error> {
+    match compression {
+        Compression::None => Ok(Box::new(reader)),
+        Compression::Gzip => Ok(Box::new(MultiGzDecoder::new(reader))),
+        #[cfg(feature = "bzip2")]
+        Compression::Bzip2 => Ok(Box::new(bzip2::read::MultiBzDecoder::new(reader))),
+        #[cfg(feature = "xz")]
+        Compression::Xz => Ok(Box::new(xz2::read::XzDecoder::new_multi_decoder(reader))),
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => Ok(Box::new(zstd_decoder(reader, options)?)),
+        Compression::Compress => Ok(Box::new(crate::lzw::LzwDecoder::new(reader))),
+        other => Err(unsupported(other)),
+    }
+}
+
+/// Open an archive file as a stream of tar data, decoding the format
+/// detected from its first bytes
+pub(crate) fn open_decoder<P: AsRef<Path>>(path: P, options: &CompressOptions) -> Result<Box<dyn Read>, io::Error> {
+    let compression = Compression::detect_file(&path)?;
+    decoder(io::BufReader::new(std::fs::File::open(path)?), compression, options)
+}
+
+/// Decompress data in the given format, with the dictionary of `options`
+pub(crate) fn decompress(
+    data: Vec<u8>,
+    compression: Compression,
+    options: &CompressOptions,
+) -> Result<Vec<u8>, io::Error> {
+    if compression == Compression::None {
+        return Ok(data);
+    }
+    let mut decompressed = Vec::new();
+    decoder(&data[..], compression, options)?.read_to_end(&mut decompressed)?;
+    Ok(decompressed)
+}
+
+/// Decompress data in the format detected from its content
+pub(crate) fn decompress_detected(data: Vec<u8>, options: &CompressOptions) -> Result<Vec<u8>, io::Error> {
+    let compression = Compression::detect(&data);
+    decompress(data, compression, options)
+}
+
+/// Streaming compressor writing to `W`
+pub(crate) enum Encoder<W: Write> {
+    None(W),
+    Gzip(GzEncoder<W>),
+    GzipParallel(crate::parallel_gzip::ParallelGzEncoder<W>),
+    GzipIndexed(crate::gzip_index::IndexedGzEncoder<W>),
+    #[cfg(feature = "bzip2")]
+    Bzip2(bzip2::write::BzEncoder<W>),
+    #[cfg(feature = "xz")]
+    Xz(xz2::write::XzEncoder<W>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::stream::write::Encoder<'static, W>),
+    #[cfg(feature = "zstd")]
+    ZstdSeekable(crate::zstd_seekable::SeekableEncoder<W>),
+}
+
+impl<W: Write> Encoder<W> {
+    pub(crate) fn new(writer: W, compression: Compression, options: &CompressOptions) -> Result<Self, io::Error> {
+        match compression {
+            Compression::None => Ok(Encoder::None(writer)),
+            Compression::Gzip => {
+                let level = flate2::Compression::new(checked_level(options, Compression::Gzip, 0..=9, 6)? as u32);
+                let header = options.gzip.resolved_header()?;
+                if options.gzip.indexed {
+                    Ok(Encoder::GzipIndexed(crate::gzip_index::IndexedGzEncoder::new(writer, level, header)))
+                } else if options.gzip.threads > 1 {
+                    let encoder = crate::parallel_gzip::ParallelGzEncoder::new(writer, level, options.gzip.threads, header);
+                    Ok(Encoder::GzipParallel(encoder))
+                } else {
+                    Ok(Encoder::Gzip(header.builder(true).write(writer, level)))
+                }
+            }
+            #[cfg(feature = "bzip2")]
+            Compression::Bzip2 => {
+                let level = checked_level(options, Compression::Bzip2, 1..=9, 9)?;
+                Ok(Encoder::Bzip2(bzip2::write::BzEncoder::new(writer, bzip2::Compression::new(level as u32))))
+            }
+            #[cfg(feature = "xz")]
+            Compression::Xz => {
+                let level = checked_level(options, Compression::Xz, 0..=9, 6)?;
+                Ok(Encoder::Xz(xz2::write::XzEncoder::new(writer, level as u32)))
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd if options.zstd.seekable => {
+                zstd_encoder(io::sink(), options)?; // validate the options up front
+                Ok(Encoder::ZstdSeekable(crate::zstd_seekable::SeekableEncoder::new(writer, options)))
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Ok(Encoder::Zstd(zstd_encoder(writer, options)?)),
+            other => Err(unsupported(other)),
+        }
+    }
+
+    /// Mark an entry boundary
+    /// Seekable formats start a new independently decodable frame here
+    pub(crate) fn end_frame(&mut self) -> Result<(), io::Error> {
+        match self {
+            Encoder::GzipIndexed(encoder) => encoder.end_frame(),
+            #[cfg(feature = "zstd")]
+            Encoder::ZstdSeekable(encoder) => encoder.end_frame(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Write the trailer and return the inner writer
+    pub(crate) fn finish(self) -> Result<W, io::Error> {
+        match self {
+            Encoder::None(writer) => Ok(writer),
+            Encoder::Gzip(encoder) => encoder.finish(),
+            Encoder::GzipParallel(encoder) => encoder.finish(),
+            Encoder::GzipIndexed(encoder) => encoder.finish(),
+            #[cfg(feature = "bzip2")]
+            Encoder::Bzip2(encoder) => encoder.finish(),
+            #[cfg(feature = "xz")]
+            Encoder::Xz(encoder) => encoder.finish(),
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(encoder) => encoder.finish(),
+            #[cfg(feature = "zstd")]
+            Encoder::ZstdSeekable(encoder) => encoder.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Encoder::None(writer) => writer.write(buf),
+            Encoder::Gzip(encoder) => encoder.write(buf),
+            Encoder::GzipParallel(encoder) => encoder.write(buf),
+            Encoder::GzipIndexed(encoder) => encoder.write(buf),
+            #[cfg(feature = "bzip2")]
+            Encoder::Bzip2(encoder) => encoder.write(buf),
+            #[cfg(feature = "xz")]
+            Encoder::Xz(encoder) => encoder.write(buf),
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(encoder) => encoder.write(buf),
+            #[cfg(feature = "zstd")]
+            Encoder::ZstdSeekable(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Encoder::None(writer) => writer.flush(),
+            Encoder::Gzip(encoder) => encoder.flush(),
+            Encoder::GzipParallel(encoder) => encoder.flush(),
+            Encoder::GzipIndexed(encoder) => encoder.flush(),
+            #[cfg(feature = "bzip2")]
+            Encoder::Bzip2(encoder) => encoder.flush(),
+            #[cfg(feature = "xz")]
+            Encoder::Xz(encoder) => encoder.flush(),
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(encoder) => encoder.flush(),
+            #[cfg(feature = "zstd")]
+            Encoder::ZstdSeekable(encoder) => encoder.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_test() {
+        let mut encoder = Encoder::new(Vec::new(), Compression::Gzip, &CompressOptions::default()).unwrap();
+        encoder.write_all(b"hello").unwrap();
+        let gz = encoder.finish().unwrap();
+        assert_eq!(Compression::detect(&gz), Compression::Gzip);
+        assert_eq!(Compression::detect(b"hello"), Compression::None);
+        assert_eq!(Compression::detect(&[]), Compression::None);
+        assert_eq!(Compression::detect(b"BZh91AY&SY"), Compression::Bzip2);
+        assert_eq!(Compression::detect(b"\xfd7zXZ\0\0"), Compression::Xz);
+        assert_eq!(Compression::detect(b"\x28\xb5\x2f\xfd\x00"), Compression::Zstd);
+        assert_eq!(Compression::detect(b"\x5e\x2a\x4d\x18\x00"), Compression::Zstd);
+        assert_eq!(Compression::detect(b"\x04\x22\x4d\x18"), Compression::Lz4);
+        assert_eq!(Compression::detect(b"\x1f\x9d\x90"), Compression::Compress);
+
+        // a plain tar whose first name looks like a magic is still plain
+        let tar = crate::tar::write_tar(&[crate::tar::TarEntry::file("BZh9.txt", b"x".to_vec())]).unwrap();
+        assert!(is_ustar(&tar));
+        assert_eq!(Compression::detect(&tar), Compression::None);
+        assert_eq!(decompress(gz, Compression::Gzip, &CompressOptions::default()).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn from_path_test() {
+        assert_eq!(Compression::from_path("a.tar.gz"), Compression::Gzip);
+        assert_eq!(Compression::from_path("a.TGZ"), Compression::Gzip);
+        assert_eq!(Compression::from_path("backup.gz"), Compression::Gzip);
+        assert_eq!(Compression::from_path("a.tbz2"), Compression::Bzip2);
+        assert_eq!(Compression::from_path("a.tar.xz"), Compression::Xz);
+        assert_eq!(Compression::from_path("a.tar.zst"), Compression::Zstd);
+        assert_eq!(Compression::from_path("a.tar.Z"), Compression::Compress);
+        assert_eq!(Compression::from_path("a.taz"), Compression::Compress);
+        assert_eq!(Compression::from_path("a.tar"), Compression::None);
+    }
+
+    #[test]
+    fn encoder_test() {
+        let mut encoder = Encoder::new(Vec::new(), Compression::Gzip, &CompressOptions::default()).unwrap();
+        encoder.write_all(b"hello ").unwrap();
+        encoder.write_all(b"world").unwrap();
+        let gz = encoder.finish().unwrap();
+        assert_eq!(decompress(gz, Compression::Gzip, &CompressOptions::default()).unwrap(), b"hello world");
+    }
+
+    #[test]
+    fn gzip_options_test() {
+        let data: Vec<u8> = (0..3 * crate::parallel_gzip::BLOCK_SIZE + 100).map(|i| (i % 251) as u8 ^ (i / 4096) as u8).collect();
+        let compress = |options: &CompressOptions| {
+            let mut encoder = Encoder::new(Vec::new(), Compression::Gzip, options).unwrap();
+            encoder.write_all(&data).unwrap();
+            encoder.finish().unwrap()
+        };
+        let fast = compress(&CompressOptions { level: Some(1), ..Default::default() });
+        let best = compress(&CompressOptions { level: Some(9), ..Default::default() });
+        assert!(best.len() < fast.len());
+
+        // parallel output is a multi-member stream of the same data
+        let options = CompressOptions { gzip: GzipOptions { threads: 4, ..Default::default() }, ..Default::default() };
+        let parallel = compress(&options);
+        assert_eq!(Compression::detect(&parallel), Compression::Gzip);
+        let mut single = flate2::read::GzDecoder::new(&parallel[..]);
+        let mut first = Vec::new();
+        single.read_to_end(&mut first).unwrap();
+        assert_eq!(first.len(), crate::parallel_gzip::BLOCK_SIZE);
+        assert_eq!(decompress_detected(parallel, &options).unwrap(), data);
+
+        let empty = Encoder::new(Vec::new(), Compression::Gzip, &options).unwrap().finish().unwrap();
+        assert_eq!(decompress_detected(empty, &options).unwrap(), b"");
+
+        let options = CompressOptions { level: Some(10), ..Default::default() };
+        assert!(Encoder::new(Vec::new(), Compression::Gzip, &options).is_err());
+    }
+
+    #[test]
+    fn gzip_header_test() {
+        let data = b"tar_light gzip header ".repeat(100);
+        let compress = |options: &CompressOptions| {
+            let mut encoder = Encoder::new(Vec::new(), Compression::Gzip, options).unwrap();
+            encoder.write_all(&data).unwrap();
+            encoder.finish().unwrap()
+        };
+        // no timestamp is written unless one is set
+        let mut options = CompressOptions::default();
+        let first = compress(&options);
+        assert_eq!(first, compress(&options));
+        assert_eq!(first[4..8], [0, 0, 0, 0]); // MTIME
+        assert_eq!(first[9], 255); // OS
+
+        options.gzip.header = GzipHeader {
+            mtime: Some(1_700_000_000),
+            operating_system: Some(3),
+            filename: Some("release.tar".to_string()),
+            comment: Some("build 42".to_string()),
+        };
+        for threads in [1, 2] {
+            options.gzip.threads = threads;
+            let gz = compress(&options);
+            let decoder = flate2::read::GzDecoder::new(&gz[..]);
+            let header = decoder.header().unwrap();
+            assert_eq!(header.mtime(), 1_700_000_000);
+            assert_eq!(header.operating_system(), 3);
+            assert_eq!(header.filename(), Some(&b"release.tar"[..]));
+            assert_eq!(header.comment(), Some(&b"build 42"[..]));
+            assert_eq!(decompress_detected(gz, &options).unwrap(), data);
+        }
+
+        options.gzip.header.filename = Some("bad\0name".to_string());
+        assert_eq!(Encoder::new(Vec::new(), Compression::Gzip, &options).err().unwrap().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_options_test() {
+        let data = b"tar_light zstd ".repeat(1000);
+        let mut dictionary = CompressOptions::default();
+        dictionary.zstd.dictionary = Some(b"tar_light zstd dictionary".to_vec());
+        for options in [
+            CompressOptions { level: Some(19), ..Default::default() },
+            CompressOptions { zstd: ZstdOptions { long_distance: true, ..Default::default() }, ..Default::default() },
+            dictionary.clone(),
+        ] {
+            let mut encoder = Encoder::new(Vec::new(), Compression::Zstd, &options).unwrap();
+            encoder.write_all(&data).unwrap();
+            let compressed = encoder.finish().unwrap();
+            assert_eq!(Compression::detect(&compressed), Compression::Zstd);
+            assert_eq!(decompress_detected(compressed, &options).unwrap(), data);
+        }
+
+        let options = CompressOptions { level: Some(99), ..Default::default() };
+        assert!(Encoder::new(Vec::new(), Compression::Zstd, &options).is_err());
+    }
+
+    #[cfg(all(feature = "bzip2", feature = "xz"))]
+    #[test]
+    fn bzip2_xz_test() {
+        let data = b"tar_light bzip2 xz ".repeat(1000);
+        for compression in [Compression::Bzip2, Compression::Xz] {
+            let options = CompressOptions { level: Some(1), ..Default::default() };
+            let mut encoder = Encoder::new(Vec::new(), compression, &options).unwrap();
+            encoder.write_all(&data).unwrap();
+            let mut compressed = encoder.finish().unwrap();
+            assert_eq!(Compression::detect(&compressed), compression);
+
+            // concatenated streams are read as one
+            compressed.extend_from_slice(&compressed.clone());
+            assert_eq!(decompress_detected(compressed, &options).unwrap(), data.repeat(2));
+
+            let options = CompressOptions { level: Some(10), ..Default::default() };
+            assert!(Encoder::new(Vec::new(), compression, &options).is_err());
+        }
+    }
+
+    #[cfg(not(feature = "zstd"))]
+    #[test]
+    fn zstd_unsupported_test() {
+        let err = Encoder::new(Vec::new(), Compression::Zstd, &CompressOptions::default()).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+        assert!(err.to_string().contains("`zstd` feature"));
+    }
+}
@@ -0,0 +1,450 @@
+//! Structural diff between two Tar archives
+//!
+//! # Usage
+//!
+//! ```rust
+//! use tar_light::{diff, Tar};
+//!
+//! let mut a = Tar::new();
+//! a.add_str_entry("README.md", "# v1\n");
+//! a.add_str_entry("old.txt", "old");
+//!
+//! let mut b = Tar::new();
+//! b.add_str_entry("README.md", "# v2\n");
+//! b.add_str_entry("new.txt", "new");
+//!
+//! let d = diff(&a, &b).unwrap();
+//! assert_eq!(d.added, vec!["new.txt"]);
+//! assert_eq!(d.removed, vec!["old.txt"]);
+//! assert_eq!(d.content_changed[0].name, "README.md");
+//! println!("{}", d);          // human readable report
+//! println!("{}", d.to_json()); // JSON report
+//! ```
+
+use crate::tar::{Tar, TarEntry};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+
+/// Options for `diff_with_options`
+#[derive(Debug, Clone)]
+pub struct DiffOptions {
+    /// Compute a unified text diff for changed UTF-8 files
+    pub unified: bool,
+    /// Number of context lines in the unified diff
+    pub context: usize,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self { unified: false, context: 3 }
+    }
+}
+
+/// An entry whose data differs between the two archives
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContentChange {
+    pub name: String,
+    pub old_size: u64,
+    pub new_size: u64,
+    /// Unified diff, if requested and both versions are UTF-8 text
+    pub unified: Option<String>,
+}
+
+/// A header field that differs between the two archives
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub old: String,
+    pub new: String,
+}
+
+/// An entry whose metadata differs between the two archives
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetadataChange {
+    pub name: String,
+    pub changes: Vec<FieldChange>,
+}
+
+/// Differences between two archives, sorted by name
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TarDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub content_changed: Vec<ContentChange>,
+    pub metadata_changed: Vec<MetadataChange>,
+}
+
+impl TarDiff {
+    /// Returns true if the archives are equivalent
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.content_changed.is_empty()
+            && self.metadata_changed.is_empty()
+    }
+
+    /// Format the report as JSON
+    pub fn to_json(&self) -> String {
+        let names = |v: &[String]| v.iter().map(|n| json_str(n)).collect::<Vec<_>>().join(", ");
+        let content = self
+            .content_changed
+            .iter()
+            .map(|c| {
+                let unified = c.unified.as_deref().map(json_str).unwrap_or_else(|| "null".to_string());
+                format!(
+                    "{{\"name\": {}, \"old_size\": {}, \"new_size\": {}, \"unified\": {}}}",
+                    json_str(&c.name),
+                    c.old_size,
+                    c.new_size,
+                    unified
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let metadata = self
+            .metadata_changed
+            .iter()
+            .map(|m| {
+                let changes = m
+                    .changes
+                    .iter()
+                    .map(|f| {
+                        format!(
+                            "{{\"field\": {}, \"old\": {}, \"new\": {}}}",
+                            json_str(f.field),
+                            json_str(&f.old),
+                            json_str(&f.new)
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!("{{\"name\": {}, \"changes\": [{}]}}", json_str(&m.name), changes)
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!(
+            "{{\"added\": [{}], \"removed\": [{}], \"content_changed\": [{}], \"metadata_changed\": [{}]}}",
+            names(&self.added),
+            names(&self.removed),
+            content,
+            metadata
+        )
+    }
+}
+
+impl fmt::Display for TarDiff {
+    /// Human readable report
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No differences");
+        }
+        for name in &self.added {
+            writeln!(f, "+ {}", name)?;
+        }
+        for name in &self.removed {
+            writeln!(f, "- {}", name)?;
+        }
+        for c in &self.content_changed {
+            writeln!(f, "M {} ({} -> {} bytes)", c.name, c.old_size, c.new_size)?;
+            if let Some(unified) = &c.unified {
+                write!(f, "{}", unified)?;
+            }
+        }
+        for m in &self.metadata_changed {
+            let fields: Vec<String> = m.changes.iter().map(|c| format!("{}: {} -> {}", c.field, c.old, c.new)).collect();
+            writeln!(f, "m {} ({})", m.name, fields.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Compare two archives
+/// Entries are matched by name, using the last occurrence of each name
+/// Fails if the data of an entry cannot be read for the comparison
+pub fn diff(a: &Tar, b: &Tar) -> Result<TarDiff, io::Error> {
+    diff_with_options(a, b, &DiffOptions::default())
+}
+
+/// Compare two archives with options
+pub fn diff_with_options(a: &Tar, b: &Tar, options: &DiffOptions) -> Result<TarDiff, io::Error> {
+    let old = latest_entries(a);
+    let new = latest_entries(b);
+    let mut result = TarDiff::default();
+    for (name, old_entry) in &old {
+        let Some(new_entry) = new.get(name) else {
+            result.removed.push(name.to_string());
+            continue;
+        };
+        if !old_entry.data.content_eq(&new_entry.data)? {
+            let unified = if options.unified
+                && let (Ok(o), Ok(n)) = (old_entry.data.bytes(), new_entry.data.bytes())
+                && let (Ok(o), Ok(n)) = (std::str::from_utf8(&o), std::str::from_utf8(&n))
+            {
+                Some(unified_diff(o, n, &format!("a/{}", name), &format!("b/{}", name), options.context))
+            } else {
+                None
+            };
+            result.content_changed.push(ContentChange {
+                name: name.to_string(),
+                old_size: old_entry.data.len(),
+                new_size: new_entry.data.len(),
+                unified,
+            });
+        }
+        let changes = metadata_changes(old_entry, new_entry);
+        if !changes.is_empty() {
+            result.metadata_changed.push(MetadataChange { name: name.to_string(), changes });
+        }
+    }
+    result.added = new.keys().filter(|n| !old.contains_key(*n)).map(|n| n.to_string()).collect();
+    Ok(result)
+}
+
+/// Last occurrence of every name, sorted by name
+fn latest_entries(tar: &Tar) -> BTreeMap<&str, &TarEntry> {
+    tar.entries.iter().map(|e| (e.header.name.as_str(), e)).collect()
+}
+
+fn metadata_changes(old: &TarEntry, new: &TarEntry) -> Vec<FieldChange> {
+    let (o, n) = (&old.header, &new.header);
+    let mut changes = Vec::new();
+    let mut check = |field: &'static str, old: String, new: String| {
+        if old != new {
+            changes.push(FieldChange { field, old, new });
+        }
+    };
+    check("type", format!("{:?}", o.kind()), format!("{:?}", n.kind()));
+    check("mode", format!("{:o}", o.mode), format!("{:o}", n.mode));
+    check("uid", o.uid.to_string(), n.uid.to_string());
+    check("gid", o.gid.to_string(), n.gid.to_string());
+    check("uname", o.uname.clone(), n.uname.clone());
+    check("gname", o.gname.clone(), n.gname.clone());
+    check("mtime", o.mtime.to_string(), n.mtime.to_string());
+    check("linkname", o.linkname.clone(), n.linkname.clone());
+    changes
+}
+
+/// Line-level edit operation
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// Largest LCS table computed before falling back to a whole-file replacement
+const MAX_LCS_CELLS: usize = 16 * 1024 * 1024;
+
+/// Create a unified diff of two texts
+pub fn unified_diff(old: &str, new: &str, old_name: &str, new_name: &str, context: usize) -> String {
+    let a: Vec<&str> = old.split_inclusive('\n').collect();
+    let b: Vec<&str> = new.split_inclusive('\n').collect();
+    let ops = diff_lines(&a, &b);
+
+    let mut out = format!("--- {}\n+++ {}\n", old_name, new_name);
+    let changed: Vec<usize> = (0..ops.len()).filter(|&i| !matches!(ops[i], Op::Equal(..))).collect();
+    let mut k = 0;
+    while k < changed.len() {
+        // extend the hunk while the next change is within 2 * context lines
+        let start = changed[k].saturating_sub(context);
+        let mut last = changed[k];
+        while k + 1 < changed.len() && changed[k + 1] - last <= 2 * context + 1 {
+            k += 1;
+            last = changed[k];
+        }
+        let end = (last + context + 1).min(ops.len());
+        k += 1;
+
+        let (mut old_start, mut new_start) = position_before(&ops, start);
+        let (mut old_len, mut new_len) = (0, 0);
+        let mut body = String::new();
+        for op in &ops[start..end] {
+            let (prefix, line) = match *op {
+                Op::Equal(i, _) => {
+                    old_len += 1;
+                    new_len += 1;
+                    (' ', a[i])
+                }
+                Op::Delete(i) => {
+                    old_len += 1;
+                    ('-', a[i])
+                }
+                Op::Insert(j) => {
+                    new_len += 1;
+                    ('+', b[j])
+                }
+            };
+            body.push(prefix);
+            body.push_str(line);
+            if !line.ends_with('\n') {
+                body.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+        // unified diff line numbers are 1-based, or 0 for an empty range
+        if old_len > 0 {
+            old_start += 1;
+        }
+        if new_len > 0 {
+            new_start += 1;
+        }
+        out.push_str(&format!("@@ -{},{} +{},{} @@\n", old_start, old_len, new_start, new_len));
+        out.push_str(&body);
+    }
+    out
+}
+
+/// Number of old and new lines before `ops[index]`
+fn position_before(ops: &[Op], index: usize) -> (usize, usize) {
+    ops[..index].iter().fold((0, 0), |(o, n), op| match op {
+        Op::Equal(..) => (o + 1, n + 1),
+        Op::Delete(_) => (o + 1, n),
+        Op::Insert(_) => (o, n + 1),
+    })
+}
+
+/// Compute line edit operations with a longest common subsequence table
+fn diff_lines(a: &[&str], b: &[&str]) -> Vec<Op> {
+    // common prefix and suffix do not need the table
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..].iter().rev().zip(b[prefix..].iter().rev()).take_while(|(x, y)| x == y).count();
+    let (am, bm) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    let mut ops: Vec<Op> = (0..prefix).map(|i| Op::Equal(i, i)).collect();
+    let (n, m) = (am.len(), bm.len());
+    if n.saturating_mul(m) > MAX_LCS_CELLS {
+        ops.extend((0..n).map(|i| Op::Delete(prefix + i)));
+        ops.extend((0..m).map(|j| Op::Insert(prefix + j)));
+    } else {
+        // lcs[i][j] = LCS length of am[i..] and bm[j..]
+        let mut lcs = vec![0u32; (n + 1) * (m + 1)];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i * (m + 1) + j] = if am[i] == bm[j] {
+                    lcs[(i + 1) * (m + 1) + j + 1] + 1
+                } else {
+                    lcs[(i + 1) * (m + 1) + j].max(lcs[i * (m + 1) + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && am[i] == bm[j] {
+                ops.push(Op::Equal(prefix + i, prefix + j));
+                i += 1;
+                j += 1;
+            } else if j < m && (i == n || lcs[i * (m + 1) + j + 1] >= lcs[(i + 1) * (m + 1) + j]) {
+                ops.push(Op::Insert(prefix + j));
+                j += 1;
+            } else {
+                ops.push(Op::Delete(prefix + i));
+                i += 1;
+            }
+        }
+    }
+    let (a_tail, b_tail) = (a.len() - suffix, b.len() - suffix);
+    ops.extend((0..suffix).map(|k| Op::Equal(a_tail + k, b_tail + k)));
+    // show deletions before insertions within a change block
+    let mut k = 0;
+    while k < ops.len() {
+        if matches!(ops[k], Op::Equal(..)) {
+            k += 1;
+            continue;
+        }
+        let start = k;
+        while k < ops.len() && !matches!(ops[k], Op::Equal(..)) {
+            k += 1;
+        }
+        ops[start..k].sort_by_key(|op| matches!(op, Op::Insert(_)));
+    }
+    ops
+}
+
+/// Quote a string as a JSON string literal
+pub(crate) fn json_str(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_entries_test() {
+        let mut a = Tar::new();
+        a.add_str_entry("same.txt", "same");
+        a.add_str_entry("changed.txt", "one\ntwo\nthree\n");
+        a.add_str_entry("removed.txt", "bye");
+        a.add_entry(TarEntry::symlink("link", "same.txt"));
+
+        let mut b = Tar::new();
+        b.add_str_entry("same.txt", "same");
+        b.add_str_entry("changed.txt", "one\n2\nthree\n");
+        b.add_str_entry("added.txt", "hi");
+        b.add_entry(TarEntry::symlink("link", "changed.txt").with_mode(0o755));
+
+        let d = diff_with_options(&a, &b, &DiffOptions { unified: true, context: 1 }).unwrap();
+        assert_eq!(d.added, vec!["added.txt"]);
+        assert_eq!(d.removed, vec!["removed.txt"]);
+        assert_eq!(d.content_changed.len(), 1);
+        assert_eq!(d.content_changed[0].name, "changed.txt");
+        assert_eq!(
+            d.content_changed[0].unified.as_deref(),
+            Some("--- a/changed.txt\n+++ b/changed.txt\n@@ -1,3 +1,3 @@\n one\n-two\n+2\n three\n")
+        );
+        assert_eq!(d.metadata_changed.len(), 1);
+        let fields: Vec<&str> = d.metadata_changed[0].changes.iter().map(|c| c.field).collect();
+        assert_eq!(fields, vec!["mode", "linkname"]);
+
+        assert!(diff(&a, &a).unwrap().is_empty());
+        assert_eq!(diff(&a, &a).unwrap().to_string(), "No differences\n");
+
+        // unreadable data is an error, not a difference
+        let path = "test_diff_unreadable.bin";
+        std::fs::write(path, "same").unwrap();
+        let mut c = Tar::new();
+        c.add_entry(TarEntry::file("same.txt", crate::EntryData::from_path(path).unwrap()));
+        assert!(diff(&b, &c).unwrap().content_changed.is_empty());
+        std::fs::remove_file(path).unwrap();
+        assert!(diff(&b, &c).is_err());
+    }
+
+    #[test]
+    fn diff_json_test() {
+        let mut a = Tar::new();
+        a.add_str_entry("quote\".txt", "x");
+        let b = Tar::new();
+        let json = diff(&a, &b).unwrap().to_json();
+        assert_eq!(
+            json,
+            "{\"added\": [], \"removed\": [\"quote\\\".txt\"], \"content_changed\": [], \"metadata_changed\": []}"
+        );
+    }
+
+    #[test]
+    fn unified_diff_test() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\n";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\nI\nj";
+        let text = unified_diff(old, new, "old", "new", 1);
+        assert_eq!(
+            text,
+            "--- old\n+++ new\n@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n@@ -8,2 +8,3 @@\n h\n-i\n+I\n+j\n\\ No newline at end of file\n"
+        );
+        // insertion into an empty file
+        assert_eq!(unified_diff("", "x\n", "old", "new", 3), "--- old\n+++ new\n@@ -0,0 +1,1 @@\n+x\n");
+    }
+}
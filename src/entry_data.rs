@@ -0,0 +1,267 @@
+//! Data source of a tar entry
+//!
+//! Entry data can be held in memory, be a byte range within a backing file,
+//! or be a filesystem path that is only opened when the data is written.
+//! `write_tar_to` and `Tar::save` stream from the source, so an archive of
+//! many large files can be built without holding them all in RAM.
+//!
+//! # Usage
+//!
+//! ```rust
+//! use tar_light::{EntryData, Tar, TarEntry};
+//!
+//! std::fs::write("entry_data_doc.txt", b"large file").unwrap();
+//!
+//! let mut tar = Tar::new();
+//! let data = EntryData::from_path("entry_data_doc.txt").unwrap();
+//! assert_eq!(data.len(), 10);
+//! tar.add_entry(TarEntry::file("large.txt", data));
+//!
+//! // The file is read while the archive is written
+//! tar.save("entry_data_doc.tar").unwrap();
+//! let saved = Tar::open("entry_data_doc.tar").unwrap();
+//! assert_eq!(saved.get_str("large.txt").as_deref(), Some("large file"));
+//! # std::fs::remove_file("entry_data_doc.txt").unwrap();
+//! # std::fs::remove_file("entry_data_doc.tar").unwrap();
+//! ```
+
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Data of a tar entry
+#[derive(Debug, Clone)]
+pub enum EntryData {
+    /// Bytes held in memory
+    Memory(Vec<u8>),
+    /// `len` bytes at `offset` within an open file
+    FileRange { file: Arc<File>, offset: u64, len: u64 },
+    /// A file that is opened when the data is read
+    /// `len` is the file size when the entry was created
+    Path { path: PathBuf, len: u64 },
+}
+
+impl Default for EntryData {
+    fn default() -> Self {
+        EntryData::Memory(Vec::new())
+    }
+}
+
+impl EntryData {
+    /// Refer to a file on disk without reading it
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, io::Error> {
+        let path = path.as_ref();
+        let len = std::fs::metadata(path)?.len();
+        Ok(EntryData::Path { path: path.to_path_buf(), len })
+    }
+
+    /// Refer to `len` bytes at `offset` within an open file
+    pub fn from_file_range(file: Arc<File>, offset: u64, len: u64) -> Self {
+        EntryData::FileRange { file, offset, len }
+    }
+
+    /// Size of the data in bytes
+    pub fn len(&self) -> u64 {
+        match self {
+            EntryData::Memory(data) => data.len() as u64,
+            EntryData::FileRange { len, .. } | EntryData::Path { len, .. } => *len,
+        }
+    }
+
+    /// Returns true if the data is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns true if the data is held in memory
+    pub fn is_loaded(&self) -> bool {
+        matches!(self, EntryData::Memory(_))
+    }
+
+    /// The data if it is held in memory
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            EntryData::Memory(data) => Some(data),
+            _ => None,
+        }
+    }
+
+    /// Open a reader over the data
+    pub fn open(&self) -> Result<Box<dyn Read + '_>, io::Error> {
+        match self {
+            EntryData::Memory(data) => Ok(Box::new(&data[..])),
+            EntryData::FileRange { file, offset, len } => Ok(Box::new(RangeReader {
+                file,
+                pos: *offset,
+                end: offset + len,
+            })),
+            EntryData::Path { path, len } => Ok(Box::new(File::open(path)?.take(*len))),
+        }
+    }
+
+    /// The data, borrowed if it is in memory and read otherwise
+    pub fn bytes(&self) -> Result<Cow<'_, [u8]>, io::Error> {
+        match self {
+            EntryData::Memory(data) => Ok(Cow::Borrowed(data)),
+            _ => Ok(Cow::Owned(self.read_all()?)),
+        }
+    }
+
+    /// Read the whole data into a new vector
+    pub fn read_all(&self) -> Result<Vec<u8>, io::Error> {
+        let mut data = Vec::with_capacity(self.len() as usize);
+        self.open()?.read_to_end(&mut data)?;
+        if data.len() as u64 != self.len() {
+            return Err(self.size_changed(data.len() as u64));
+        }
+        Ok(data)
+    }
+
+    /// Compare the contents with another entry's data
+    /// Lazy sources are read; read errors are returned, not treated as a difference
+    pub fn content_eq(&self, other: &EntryData) -> Result<bool, io::Error> {
+        if self.len() != other.len() {
+            return Ok(false);
+        }
+        Ok(self.bytes()? == other.bytes()?)
+    }
+
+    /// Read the data into memory, replacing a lazy source
+    pub fn load(&mut self) -> Result<(), io::Error> {
+        if !self.is_loaded() {
+            *self = EntryData::Memory(self.read_all()?);
+        }
+        Ok(())
+    }
+
+    /// Copy the data to a writer
+    /// Fails if the source no longer has the expected size
+    pub fn write_to<W: Write + ?Sized>(&self, writer: &mut W) -> Result<u64, io::Error> {
+        if let EntryData::Memory(data) = self {
+            writer.write_all(data)?;
+            return Ok(data.len() as u64);
+        }
+        let copied = io::copy(&mut self.open()?, writer)?;
+        if copied != self.len() {
+            return Err(self.size_changed(copied));
+        }
+        Ok(copied)
+    }
+
+    fn size_changed(&self, actual: u64) -> io::Error {
+        let source = match self {
+            EntryData::Path { path, .. } => path.display().to_string(),
+            _ => "entry data".to_string(),
+        };
+        io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("{} changed size: expected {} bytes, read {}", source, self.len(), actual),
+        )
+    }
+}
+
+/// Reads a byte range of a shared file without moving its cursor
+struct RangeReader<'a> {
+    file: &'a File,
+    pos: u64,
+    end: u64,
+}
+
+impl Read for RangeReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let want = buf.len().min((self.end - self.pos) as usize);
+        if want == 0 {
+            return Ok(0);
+        }
+        let n = self.file.read_at(&mut buf[..want], self.pos)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl From<Vec<u8>> for EntryData {
+    fn from(data: Vec<u8>) -> Self {
+        EntryData::Memory(data)
+    }
+}
+
+impl From<&[u8]> for EntryData {
+    fn from(data: &[u8]) -> Self {
+        EntryData::Memory(data.to_vec())
+    }
+}
+
+impl From<&str> for EntryData {
+    fn from(data: &str) -> Self {
+        EntryData::Memory(data.as_bytes().to_vec())
+    }
+}
+
+impl PartialEq<[u8]> for EntryData {
+    fn eq(&self, other: &[u8]) -> bool {
+        self.len() == other.len() as u64 && self.bytes().is_ok_and(|data| *data == *other)
+    }
+}
+
+impl PartialEq<&[u8]> for EntryData {
+    fn eq(&self, other: &&[u8]) -> bool {
+        *self == **other
+    }
+}
+
+impl PartialEq<Vec<u8>> for EntryData {
+    fn eq(&self, other: &Vec<u8>) -> bool {
+        *self == other[..]
+    }
+}
+
+impl<const N: usize> PartialEq<[u8; N]> for EntryData {
+    fn eq(&self, other: &[u8; N]) -> bool {
+        *self == other[..]
+    }
+}
+
+impl<const N: usize> PartialEq<&[u8; N]> for EntryData {
+    fn eq(&self, other: &&[u8; N]) -> bool {
+        *self == other[..]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entry_data_sources_test() {
+        let path = "test_entry_data_sources.bin";
+        std::fs::write(path, b"0123456789").unwrap();
+
+        let memory = EntryData::from(b"0123456789".to_vec());
+        let lazy = EntryData::from_path(path).unwrap();
+        let range = EntryData::from_file_range(Arc::new(File::open(path).unwrap()), 2, 5);
+        assert_eq!(lazy.len(), 10);
+        assert!(!lazy.is_loaded());
+        assert!(lazy.content_eq(&memory).unwrap());
+        assert!(!range.content_eq(&memory).unwrap());
+        assert_eq!(range.read_all().unwrap(), b"23456");
+        assert_eq!(range, b"23456");
+
+        let mut out = Vec::new();
+        assert_eq!(range.write_to(&mut out).unwrap(), 5);
+        assert_eq!(out, b"23456");
+
+        let mut loaded = lazy.clone();
+        loaded.load().unwrap();
+        assert_eq!(loaded.as_bytes(), Some(&b"0123456789"[..]));
+
+        // a file that shrank since the entry was created is an error
+        std::fs::write(path, b"0123").unwrap();
+        assert_eq!(lazy.read_all().unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        assert!(lazy.write_to(&mut Vec::new()).is_err());
+        assert!(lazy.content_eq(&memory).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
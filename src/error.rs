@@ -0,0 +1,76 @@
+//! Error type of the archive functions
+//!
+//! `TarError` wraps the underlying `io::Error` together with the file it
+//! concerns, so a caller gets "archive.tar.gz: unexpected end of file"
+//! instead of a bare message. It converts to and from `io::Error`, so
+//! functions returning `io::Result` can still use `?`.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Error of an archive operation
+#[derive(Debug)]
+pub struct TarError {
+    path: Option<PathBuf>,
+    source: io::Error,
+}
+
+impl TarError {
+    /// Error concerning the file at `path`
+    pub fn new<P: AsRef<Path>>(path: P, source: io::Error) -> Self {
+        Self { path: Some(path.as_ref().to_path_buf()), source }
+    }
+
+    /// Kind of the underlying I/O error
+    pub fn kind(&self) -> io::ErrorKind {
+        self.source.kind()
+    }
+
+    /// The file the error concerns, if known
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// The underlying I/O error
+    pub fn io_error(&self) -> &io::Error {
+        &self.source
+    }
+}
+
+/// Attach `path` to an I/O error, for use with `map_err`
+pub(crate) fn at<P: AsRef<Path>>(path: P) -> impl FnOnce(io::Error) -> TarError {
+    move |e| TarError::new(path, e)
+}
+
+impl fmt::Display for TarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "{}: {}", path.display(), self.source),
+            None => write!(f, "{}", self.source),
+        }
+    }
+}
+
+impl Error for TarError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+impl From<io::Error> for TarError {
+    fn from(source: io::Error) -> Self {
+        Self { path: None, source }
+    }
+}
+
+/// Keeps the error kind; the path becomes part of the message
+impl From<TarError> for io::Error {
+    fn from(e: TarError) -> Self {
+        match e.path {
+            Some(_) => io::Error::new(e.kind(), e.to_string()),
+            None => e.source,
+        }
+    }
+}
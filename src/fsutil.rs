@@ -0,0 +1,64 @@
+//! Filesystem helpers for durable and atomic writes
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Temporary file next to `path`, used for atomic replacement
+pub(crate) fn temp_path_for(path: &Path) -> PathBuf {
+    let file_name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    path.with_file_name(format!(".{}.tmp-{}", file_name, std::process::id()))
+}
+
+/// fsync the directory containing `path` so a create or rename is durable
+pub(crate) fn sync_parent_dir(path: &Path) -> Result<(), io::Error> {
+    let parent = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    File::open(parent)?.sync_all()
+}
+
+/// Replace `path` so that a crash leaves either the old or the new file
+///
+/// `write` streams the content to a temporary file in the same directory,
+/// which is fsync'ed and renamed over `path`.
+pub(crate) fn write_atomic_with<F>(path: &Path, write: F) -> Result<(), io::Error>
+where
+    F: FnOnce(&mut io::BufWriter<&File>) -> Result<(), io::Error>,
+{
+    let tmp_path = temp_path_for(path);
+    let result = (|| {
+        let tmp = File::create(&tmp_path)?;
+        let mut writer = io::BufWriter::new(&tmp);
+        write(&mut writer)?;
+        writer.flush()?;
+        drop(writer);
+        tmp.sync_all()?;
+        fs::rename(&tmp_path, path)
+    })();
+    if result.is_err() {
+        fs::remove_file(&tmp_path).ok();
+    }
+    result?;
+    sync_parent_dir(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_atomic_test() {
+        let path = Path::new("test_fsutil_atomic.bin");
+        fs::write(path, b"old").unwrap();
+        write_atomic_with(path, |w| w.write_all(b"new")).unwrap();
+        assert_eq!(fs::read(path).unwrap(), b"new");
+        assert!(!temp_path_for(path).exists());
+        fs::remove_file(path).unwrap();
+
+        // a failed write leaves no temporary file behind
+        let bad = Path::new("no_such_dir_for_atomic/file.bin");
+        assert!(write_atomic_with(bad, |w| w.write_all(b"x")).is_err());
+    }
+}
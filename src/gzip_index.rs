@@ -0,0 +1,369 @@
+//! Indexed gzip archives
+//!
+//! An indexed `.tar.gz` compresses every entry as its own gzip member and
+//! ends with an index of the members and the entry names, so a single entry
+//! can be extracted by decompressing only its member, similar to BGZF. The
+//! index is stored in the extra field of empty gzip members: `gunzip`,
+//! `tar` and other gzip readers decompress them to nothing and see an
+//! ordinary `.tar.gz`.
+//!
+//! Layout after the data members:
+//! - index members with subfield `TI`: for each data member, its compressed
+//!   size and size as little-endian u64, then the name of the entry it holds
+//!   as a little-endian u16 length and UTF-8 bytes (empty for the
+//!   end-of-archive member); records do not span index members
+//! - a fixed-size footer member with subfield `TF`: the offset of the
+//!   first index member and the number of data members
+//!
+//! # Usage
+//!
+//! ```rust
+//! use tar_light::{CompressOptions, IndexedGzip, Tar};
+//!
+//! let mut tar = Tar::new();
+//! tar.add_str_entry("a.txt", "alpha");
+//! tar.add_str_entry("b.txt", "beta");
+//! let mut options = CompressOptions::default();
+//! options.gzip.indexed = true;
+//! tar.save_with("indexed_doc.tar.gz", &options).unwrap();
+//!
+//! let archive = IndexedGzip::open("indexed_doc.tar.gz").unwrap();
+//! let entry = archive.read_entry("b.txt").unwrap().unwrap();
+//! assert_eq!(entry.data, b"beta");
+//! # std::fs::remove_file("indexed_doc.tar.gz").unwrap();
+//! ```
+
+use crate::compression::GzipHeader;
+use crate::random_access::{self, CountingWriter, Frame};
+use crate::tar::{self, TarEntry, TarHeader};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+
+/// Subfield id of the index members
+const INDEX_ID: [u8; 2] = *b"TI";
+/// Subfield id of the footer member
+const FOOTER_ID: [u8; 2] = *b"TF";
+/// Size of a member record in the index without the name
+const RECORD_SIZE: usize = 18;
+/// Largest payload of an index member, so the subfield fits the 16-bit extra length
+const MAX_PAYLOAD: usize = u16::MAX as usize - 4;
+/// Gzip header up to the extra length: magic, CM, FLG=FEXTRA, MTIME, XFL, OS
+const EXTRA_HEADER: [u8; 10] = [0x1f, 0x8b, 8, 4, 0, 0, 0, 0, 0, 255];
+/// Empty deflate stream followed by CRC32 and ISIZE of no data
+const EMPTY_BODY: [u8; 10] = [3, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+/// Size of the footer member: header, extra length, subfield, body
+const FOOTER_SIZE: u64 = 10 + 2 + 4 + 16 + 10;
+
+/// Writes one gzip member per entry followed by the member index
+pub(crate) struct IndexedGzEncoder<W: Write> {
+    level: flate2::Compression,
+    header: GzipHeader,
+    writer: Option<CountingWriter<W>>,
+    member: Option<GzEncoder<CountingWriter<W>>>,
+    member_start: u64,
+    member_size: u64,
+    /// First bytes of the current member, holding the entry header
+    member_head: Vec<u8>,
+    members: Vec<(u64, u64, String)>,
+}
+
+impl<W: Write> IndexedGzEncoder<W> {
+    pub(crate) fn new(writer: W, level: flate2::Compression, header: GzipHeader) -> Self {
+        Self {
+            level,
+            header,
+            writer: Some(CountingWriter::new(writer)),
+            member: None,
+            member_start: 0,
+            member_size: 0,
+            member_head: Vec::new(),
+            members: Vec::new(),
+        }
+    }
+
+    /// Close the current member; the next write starts a new one
+    pub(crate) fn end_frame(&mut self) -> Result<(), io::Error> {
+        let Some(member) = self.member.take() else {
+            return Ok(());
+        };
+        let writer = member.finish()?;
+        let name = if self.member_head.len() == 512 && !tar::is_empty_block(&self.member_head) {
+            tar::parse_tar_header(&self.member_head).name
+        } else {
+            String::new()
+        };
+        self.members.push((writer.count - self.member_start, self.member_size, name));
+        self.writer = Some(writer);
+        self.member_size = 0;
+        self.member_head.clear();
+        Ok(())
+    }
+
+    /// Close the last member, write the index and return the inner writer
+    pub(crate) fn finish(mut self) -> Result<W, io::Error> {
+        self.end_frame()?;
+        let mut writer = self.writer.take().expect("no open member after end_frame");
+        let index_offset = writer.count;
+        let mut payload = Vec::new();
+        for (compressed, size, name) in &self.members {
+            // names come from a 512-byte header, so a record always fits
+            if payload.len() + RECORD_SIZE + name.len() > MAX_PAYLOAD {
+                writer.write_all(&extra_member(INDEX_ID, &payload))?;
+                payload.clear();
+            }
+            payload.extend_from_slice(&compressed.to_le_bytes());
+            payload.extend_from_slice(&size.to_le_bytes());
+            payload.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            payload.extend_from_slice(name.as_bytes());
+        }
+        if !payload.is_empty() {
+            writer.write_all(&extra_member(INDEX_ID, &payload))?;
+        }
+        let mut footer = index_offset.to_le_bytes().to_vec();
+        footer.extend_from_slice(&(self.members.len() as u64).to_le_bytes());
+        writer.write_all(&extra_member(FOOTER_ID, &footer))?;
+        Ok(writer.inner)
+    }
+}
+
+impl<W: Write> Write for IndexedGzEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.member.is_none() {
+            let writer = self.writer.take().expect("no open member without a writer");
+            self.member_start = writer.count;
+            self.member = Some(self.header.builder(self.members.is_empty()).write(writer, self.level));
+        }
+        let n = self.member.as_mut().expect("member was just opened").write(buf)?;
+        let head = n.min(512 - self.member_head.len());
+        self.member_head.extend_from_slice(&buf[..head]);
+        self.member_size += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match (&mut self.member, &mut self.writer) {
+            (Some(member), _) => member.flush(),
+            (None, Some(writer)) => writer.flush(),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// An empty gzip member carrying `payload` in an extra subfield
+fn extra_member(id: [u8; 2], payload: &[u8]) -> Vec<u8> {
+    let mut member = EXTRA_HEADER.to_vec();
+    member.extend_from_slice(&(payload.len() as u16 + 4).to_le_bytes());
+    member.extend_from_slice(&id);
+    member.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+    member.extend_from_slice(payload);
+    member.extend_from_slice(&EMPTY_BODY);
+    member
+}
+
+/// Parse an empty member written by `extra_member`
+/// Returns the subfield id, its payload and the member size
+fn parse_extra_member(data: &[u8]) -> Option<([u8; 2], &[u8], usize)> {
+    if data.get(..4)? != &EXTRA_HEADER[..4] {
+        return None;
+    }
+    let xlen = u16::from_le_bytes([*data.get(10)?, *data.get(11)?]) as usize;
+    let id = [*data.get(12)?, *data.get(13)?];
+    let len = u16::from_le_bytes([*data.get(14)?, *data.get(15)?]) as usize;
+    if len + 4 != xlen || data.get(12 + xlen..12 + xlen + EMPTY_BODY.len())? != EMPTY_BODY {
+        return None;
+    }
+    Some((id, &data[16..16 + len], 12 + xlen + EMPTY_BODY.len()))
+}
+
+/// Random access to an indexed `.tar.gz` archive
+#[derive(Debug)]
+pub struct IndexedGzip {
+    file: File,
+    members: Vec<Frame>,
+    /// Name of the entry held by each member
+    names: Vec<String>,
+}
+
+impl IndexedGzip {
+    /// Open an indexed archive and read its index
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, io::Error> {
+        let file = File::open(path)?;
+        let (members, names) = read_index(&file)?;
+        Ok(Self { file, members, names })
+    }
+
+    /// Number of data members in the archive
+    pub fn member_count(&self) -> usize {
+        self.members.len()
+    }
+
+    /// Size of the decompressed tar stream
+    pub fn decompressed_len(&self) -> u64 {
+        random_access::decompressed_len(&self.members)
+    }
+
+    /// Decompress `len` bytes at `offset` of the tar stream
+    /// Only the members covering the range are read
+    pub fn read_at(&self, offset: u64, len: u64) -> Result<Vec<u8>, io::Error> {
+        random_access::read_range(&self.file, &self.members, offset, len, |member| Ok(Box::new(GzDecoder::new(member))))
+    }
+
+    /// Headers of every entry in the archive, like `list`
+    pub fn headers(&self) -> Result<Vec<TarHeader>, io::Error> {
+        let scanned = random_access::scan(self.decompressed_len(), |offset, len| self.read_at(offset, len))?;
+        Ok(scanned.into_iter().map(|(h, _, _)| h).collect())
+    }
+
+    /// Names of the entries in the archive, taken from the index
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.names.iter().map(String::as_str).filter(|name| !name.is_empty())
+    }
+
+    /// Extract one entry by name (last occurrence)
+    /// The entry is found in the index, and only its member is decompressed
+    pub fn read_entry(&self, name: &str) -> Result<Option<TarEntry>, io::Error> {
+        let Some(i) = self.names.iter().rposition(|n| n == name) else {
+            return Ok(None);
+        };
+        let mut member = GzDecoder::new(random_access::FrameReader::new(&self.file, &self.members[i]));
+        let mut header_bytes = [0u8; 512];
+        member.read_exact(&mut header_bytes)?;
+        let header = tar::parse_tar_header(&header_bytes);
+        if header.name != name {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("index does not match the entry: {}", name)));
+        }
+        let mut data = Vec::new();
+        if header.is_file() {
+            member.take(header.size).read_to_end(&mut data)?;
+            if (data.len() as u64) < header.size {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, format!("truncated entry: {}", name)));
+            }
+        }
+        Ok(Some(TarEntry { header, data: data.into(), header_bytes }))
+    }
+}
+
+/// Parse the member index at the end of an indexed archive
+/// Returns the members and the entry name of each
+fn read_index(file: &File) -> Result<(Vec<Frame>, Vec<String>), io::Error> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, format!("not an indexed gzip archive: {}", msg));
+    let len = file.metadata()?.len();
+    if len < FOOTER_SIZE {
+        return Err(invalid("file too short"));
+    }
+    let mut footer = [0u8; FOOTER_SIZE as usize];
+    file.read_exact_at(&mut footer, len - FOOTER_SIZE)?;
+    let (index_offset, count) = match parse_extra_member(&footer) {
+        Some((FOOTER_ID, payload, _)) if payload.len() == 16 => (
+            u64::from_le_bytes(payload[0..8].try_into().unwrap()),
+            u64::from_le_bytes(payload[8..16].try_into().unwrap()),
+        ),
+        _ => return Err(invalid("missing index footer")),
+    };
+    if index_offset > len - FOOTER_SIZE {
+        return Err(invalid("index offset past the end of the file"));
+    }
+
+    let mut index = vec![0u8; (len - FOOTER_SIZE - index_offset) as usize];
+    file.read_exact_at(&mut index, index_offset)?;
+    let (mut members, mut names) = (Vec::new(), Vec::new());
+    let (mut compressed_offset, mut offset) = (0u64, 0u64);
+    let mut pos = 0;
+    while pos < index.len() {
+        let Some((INDEX_ID, mut payload, size)) = parse_extra_member(&index[pos..]) else {
+            return Err(invalid("bad index member"));
+        };
+        while !payload.is_empty() {
+            let record = payload.get(..RECORD_SIZE).ok_or_else(|| invalid("bad index record"))?;
+            let compressed_size = u64::from_le_bytes(record[0..8].try_into().unwrap());
+            let size = u64::from_le_bytes(record[8..16].try_into().unwrap());
+            let name_len = u16::from_le_bytes(record[16..18].try_into().unwrap()) as usize;
+            let name = payload.get(RECORD_SIZE..RECORD_SIZE + name_len).ok_or_else(|| invalid("bad index record"))?;
+            names.push(String::from_utf8(name.to_vec()).map_err(|_| invalid("bad entry name"))?);
+            members.push(Frame { compressed_offset, compressed_size, offset, size });
+            compressed_offset += compressed_size;
+            offset += size;
+            payload = &payload[RECORD_SIZE + name_len..];
+        }
+        pos += size;
+    }
+    if members.len() as u64 != count || compressed_offset != index_offset {
+        return Err(invalid("index does not match the data members"));
+    }
+    Ok((members, names))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::CompressOptions;
+    use crate::tar::Tar;
+
+    #[test]
+    fn indexed_round_trip_test() {
+        let path = "test_gzip_indexed.tar.gz";
+        let mut tar = Tar::new();
+        tar.add_str_entry("a.txt", "alpha");
+        tar.add_entry(TarEntry::file("big.bin", vec![9u8; 100_000]));
+        tar.add_entry(TarEntry::symlink("link", "a.txt"));
+        tar.add_str_entry("c.txt", "gamma");
+        let mut options = CompressOptions::default();
+        options.gzip.indexed = true;
+        tar.save_with(path, &options).unwrap();
+
+        // one member per entry plus the end-of-archive marker
+        let archive = IndexedGzip::open(path).unwrap();
+        assert_eq!(archive.member_count(), 5);
+        assert_eq!(archive.decompressed_len(), tar.to_bytes().unwrap().len() as u64);
+        let names: Vec<String> = archive.headers().unwrap().into_iter().map(|h| h.name).collect();
+        assert_eq!(names, vec!["a.txt", "big.bin", "link", "c.txt"]);
+        assert_eq!(archive.names().collect::<Vec<_>>(), vec!["a.txt", "big.bin", "link", "c.txt"]);
+        assert_eq!(archive.read_entry("c.txt").unwrap().unwrap().data, b"gamma");
+        assert_eq!(archive.read_entry("big.bin").unwrap().unwrap().data.len(), 100_000);
+        assert_eq!(archive.read_entry("link").unwrap().unwrap().header.linkname, "a.txt");
+        assert!(archive.read_entry("missing").unwrap().is_none());
+
+        // regular gzip readers see an ordinary .tar.gz
+        assert_eq!(Tar::open(path).unwrap().to_bytes().unwrap(), tar.to_bytes().unwrap());
+
+        // only the member of the entry is decompressed
+        let mut data = std::fs::read(path).unwrap();
+        let big = archive.members[1];
+        data[big.compressed_offset as usize + 20..(big.compressed_offset + big.compressed_size) as usize].fill(0xff);
+        std::fs::write(path, &data).unwrap();
+        let archive = IndexedGzip::open(path).unwrap();
+        assert_eq!(archive.read_entry("c.txt").unwrap().unwrap().data, b"gamma");
+        assert!(archive.read_entry("big.bin").is_err());
+
+        // large indexes span several index members
+        let mut tar = Tar::new();
+        let count = MAX_PAYLOAD / RECORD_SIZE + 10;
+        for i in 0..count {
+            tar.add_str_entry(&format!("{}.txt", i), &i.to_string());
+        }
+        tar.save_with(path, &options).unwrap();
+        let archive = IndexedGzip::open(path).unwrap();
+        assert_eq!(archive.member_count(), count + 1);
+        assert_eq!(archive.read_entry("3600.txt").unwrap().unwrap().data, b"3600");
+        assert_eq!(archive.read_at(0, 512).unwrap()[..5], *b"0.txt");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn index_errors_test() {
+        let path = "test_gzip_not_indexed.tar.gz";
+        let mut tar = Tar::new();
+        tar.add_str_entry("a.txt", "alpha");
+        tar.save(path).unwrap();
+        let err = IndexedGzip::open(path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -9,10 +9,11 @@
 //!
 //! let files = vec!["testdata/file1.txt", "testdata/file2.txt"];
 //! 
-//! pack("archive.tar", &files);
+//! pack("archive.tar", &files).unwrap();
 //! // Creates archive.tar containing file1.txt and file2.txt
-//! pack("archive.tar.gz", &files);
+//! let report = pack("archive.tar.gz", &files).unwrap();
 //! // Creates archive.tar.gz that is gzip-compressed
+//! assert_eq!(report.count(), 2);
 //! ```
 //!
 //! ## Unpacking files from a TAR archive
@@ -20,10 +21,13 @@
 //! ```rust
 //! use tar_light::unpack;
 //!
-//! unpack("testdata/simple.tar", "output_directory");
+//! unpack("testdata/simple.tar", "output_directory").unwrap();
 //! // Extracts all files from simple.tar to output_directory/
-//! unpack("testdata/simple.tar.gz", "output_directory");
-//! // Extracts all files from simple.tar.gz that is gzip-compressed
+//! let report = unpack("testdata/simple.tar.gz", "output_directory").unwrap();
+//! // Existing files are kept; they are listed in report.skipped
+//! for name in &report.skipped {
+//!     println!("Skipped: {}", name);
+//! }
 //! ```
 //!
 //! ## Listing files in a TAR archive header
@@ -34,7 +38,7 @@
 //! match list("archive.tar") {
 //!     Ok(headers) => {
 //!         println!("Files in archive:");
-//!         for header in headers {
+//!         for header in headers.iter().filter(|h| h.is_file()) {
 //!             println!("  {} ({} bytes)", header.name, header.size);
 //!         }
 //!     }
@@ -74,32 +78,58 @@
 //! }
 //! 
 //! // Write entries
-//! let tar_bytes = write_tar(&entries);
+//! let tar_bytes = write_tar(&entries).unwrap();
 //! fs::write("archive.tar", tar_bytes).unwrap();
 //! 
 //! // Create tar archive from scratch
 //! let mut tar = Tar::new();
 //! tar.add_str_entry("file1.txt", "Hello, World!");
 //! tar.add_str_entry("file2.txt", "This is a test.");
-//! let tar_bytes = tar.to_bytes();
+//! let tar_bytes = tar.to_bytes().unwrap();
 //! fs::write("archive.tar", tar_bytes).unwrap();
 //! ```
 
+pub mod compression;
+pub mod diff;
+pub mod entry_data;
+pub mod error;
+mod fsutil;
+pub mod gzip_index;
+mod lzw;
+pub mod merge;
+pub mod pack;
+mod parallel_gzip;
+mod random_access;
+pub mod report;
+pub mod store;
 pub mod tar;
+pub mod tree;
+#[cfg(feature = "zstd")]
+pub mod zstd_seekable;
 
+use std::collections::HashMap;
 use std::fs;
-use std::os::unix::fs::MetadataExt;
-use std::path::Path;
-use std::io::{Write, Read};
-use flate2::read::GzDecoder;
-use flate2::write::GzEncoder;
-use flate2::Compression;
-use std::io::{self, BufRead};
+use std::path::{Component, Path};
+use std::io::{Write, Read, Seek};
+use std::io;
 
 #[cfg(unix)]
 use std::ffi::CStr;
 
-pub use tar::{read_tar, write_tar, Tar, TarEntry, TarHeader};
+pub use compression::{CompressOptions, Compression, GzipHeader, GzipOptions, ZstdOptions};
+pub use diff::{diff, diff_with_options, DiffOptions, TarDiff};
+pub use entry_data::EntryData;
+pub use error::TarError;
+use error::at;
+pub use gzip_index::IndexedGzip;
+pub use merge::{MergePolicy, MergeReport};
+pub use pack::{ArchiveFormat, PackOptions, SymlinkPolicy};
+pub use report::Report;
+pub use store::TarStore;
+pub use tar::{read_tar, read_tar_file, write_tar, write_tar_to, EntryKind, Tar, TarEntry, TarEntryMut, TarHeader, TarHeaderBuilder, TarReader};
+pub use tree::{DirEntry, Metadata, TarTree};
+#[cfg(feature = "zstd")]
+pub use zstd_seekable::SeekableZstd;
 
 // ----------------------------------------------------------------
 // Helper functions for gzip compression/decompression
@@ -156,165 +186,221 @@ fn get_groupname_from_gid(_gid: u32) -> Option<String> {
 }
 
 // ----------------------------------------------------------------
-// Helper functions for gzip compression/decompression
+// simple methods for reading and writing tar archives
 // ----------------------------------------------------------------
-/// Checks if filename indicates gzip compression
-fn is_gzipped(filename: &str) -> bool {
-    filename.ends_with(".tar.gz") || filename.ends_with(".tgz")
+/// Packs files into a tar archive (supports .tar and .tar.gz)
+/// The compression is chosen from the file name suffix
+/// File data is streamed into the archive one file at a time
+/// Missing input files are skipped and listed in `Report::warnings`
+pub fn pack(tarfile: &str, files: &[&str]) -> Result<Report, TarError> {
+    pack_with_options(tarfile, files, &PackOptions::default())
 }
 
-/// Decompresses gzipped data if the filename suggests it's compressed
-/// Returns the raw data unchanged if not gzipped
-fn ungzip(filename: &str, data: Vec<u8>) -> Result<Vec<u8>, std::io::Error> {
-    if is_gzipped(filename) {
-        let mut decoder = GzDecoder::new(&data[..]);
-        let mut decompressed = Vec::new();
-        decoder.read_to_end(&mut decompressed)?;
-        Ok(decompressed)
-    } else {
-        Ok(data)
-    }
+/// Packs files into a tar archive with compression settings
+///
+/// The codec is chosen from the file name suffix as in `pack`; `options`
+/// sets the level and the zstd long-distance matching, dictionary and
+/// seekable format. Same as `pack_with_options` with
+/// `PackOptions::compress_options`.
+pub fn pack_compressed(tarfile: &str, files: &[&str], options: &CompressOptions) -> Result<Report, TarError> {
+    pack_with_options(tarfile, files, &PackOptions::new().compress_options(options.clone()))
 }
 
-/// Compresses data with gzip if the filename suggests it should be compressed
-/// Returns the raw data unchanged if not a gzip filename
-fn gzip(filename: &str, data: Vec<u8>) -> Result<Vec<u8>, std::io::Error> {
-    if is_gzipped(filename) {
-        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
-        encoder.write_all(&data)?;
-        encoder.finish()
-    } else {
-        Ok(data)
+/// Packs files into a tar archive with the settings of `options`
+///
+/// See `PackOptions` for the codec, header format, symlink, exclusion,
+/// naming, ownership and reproducibility settings.
+pub fn pack_with_options(tarfile: &str, files: &[&str], options: &PackOptions) -> Result<Report, TarError> {
+    let mut report = Report::default();
+    let entries = pack::collect_entries(files, options, &|_| true, &mut report.warnings);
+    let compression = options.compression_for(tarfile);
+    write_archive(tarfile, &entries, compression, options.compress()).map_err(at(tarfile))?;
+    report.entries = entries.into_iter().map(|e| e.header.name).collect();
+    Ok(report)
+}
+
+/// Streams entries into a new archive file
+/// A partially written file is removed on failure
+fn write_archive(tarfile: &str, entries: &[TarEntry], compression: Compression, options: &CompressOptions) -> Result<(), std::io::Error> {
+    let file = fs::File::create(tarfile)?;
+    let result = (|| {
+        let mut encoder = compression::Encoder::new(io::BufWriter::new(file), compression, options)?;
+        tar::write_tar_encoded(entries, &mut encoder)?;
+        encoder.finish()?.flush()
+    })();
+    if result.is_err() {
+        fs::remove_file(tarfile).ok();
     }
+    result
 }
 
-// ----------------------------------------------------------------
-// Helper functions for recursive directory packing
-// ----------------------------------------------------------------
-/// Adds a single file to entries
-fn add_file_to_entries(file_path: &Path, base_path: &Path, entries: &mut Vec<TarEntry>) {
-    let data = match fs::read(file_path) {
-        Ok(d) => d,
-        Err(e) => {
-            eprintln!("Error reading {}: {}", file_path.display(), e);
-            return;
-        }
-    };
-    
-    // Calculate relative path from base_path
-    let relative_path = file_path.strip_prefix(base_path)
-        .unwrap_or(file_path)
-        .to_string_lossy()
-        .to_string();
-
-    let mut header = TarHeader::new(
-        relative_path,
-        0o644,
-        data.len() as u64       
-    );
-    // get file metadata
-    match fs::metadata(file_path) {
-        Ok(m) => {
-            header.mode = m.mode() as u32;
-            header.mtime = m.modified().unwrap_or(std::time::SystemTime::UNIX_EPOCH)
-                .duration_since(std::time::SystemTime::UNIX_EPOCH).unwrap_or_default().as_secs();
-            header.gid = m.gid();
-            header.uid = m.uid();
-            // Set uname and gname from uid/gid
-            if let Some(uname) = get_username_from_uid(m.uid()) {
-                header.uname = uname;
-            }
-            if let Some(gname) = get_groupname_from_gid(m.gid()) {
-                header.gname = gname;
-            }
-        },
-        Err(e) => {
-            eprintln!("Error getting metadata for {}: {}", file_path.display(), e);
-            return;
-        }
-    };    let header_bytes = header.to_bytes();
-    
-    entries.push(TarEntry {
-        header,
-        data,
-        header_bytes,
-    });
+/// Appends files to the end of an uncompressed tar archive (like `tar -r`)
+///
+/// The end-of-archive marker is located by walking the headers, and the new
+/// entries and a fresh end marker are written there. Existing entries are not
+/// touched. The archive is created if it does not exist.
+pub fn append(tarfile: &str, files: &[&str]) -> Result<Report, TarError> {
+    check_appendable(tarfile).map_err(at(tarfile))?;
+    let mut report = Report::default();
+    let entries = pack::collect_entries(files, &PackOptions::default(), &|_| true, &mut report.warnings);
+    append_entries(tarfile, &entries).map_err(at(tarfile))?;
+    report.entries = entries.into_iter().map(|e| e.header.name).collect();
+    Ok(report)
 }
 
-/// Recursively collects all files from a directory
-fn collect_files_from_dir(dir_path: &Path, base_path: &Path, entries: &mut Vec<TarEntry>) {
-    let read_dir = match fs::read_dir(dir_path) {
-        Ok(d) => d,
-        Err(e) => {
-            eprintln!("Error reading directory {}: {}", dir_path.display(), e);
-            return;
+/// Appends only files that are newer than their archived copies (like `tar -u`)
+///
+/// Each file's mtime is compared with the latest entry of the same name in
+/// the archive; files that are missing from the archive or have a newer
+/// mtime are appended in place as with `append`. Unchanged files are not read.
+pub fn update(tarfile: &str, files: &[&str]) -> Result<Report, TarError> {
+    check_appendable(tarfile).map_err(at(tarfile))?;
+    let mut archived: HashMap<String, u64> = HashMap::new();
+    if Path::new(tarfile).exists() {
+        // later occurrences overwrite earlier ones
+        for header in list(tarfile)? {
+            archived.insert(header.name, header.mtime);
         }
+    }
+    let is_newer = |header: &TarHeader| match archived.get(&header.name) {
+        Some(&mtime) => header.mtime > mtime,
+        None => true,
     };
-    
-    for entry_result in read_dir {
-        let entry = match entry_result {
-            Ok(e) => e,
-            Err(e) => {
-                eprintln!("Error reading directory entry: {}", e);
-                continue;
-            }
-        };
-        
-        let path = entry.path();
-        
-        if path.is_dir() {
-            // Recursively process subdirectory
-            collect_files_from_dir(&path, base_path, entries);
-        } else if path.is_file() {
-            // Add file to entries
-            add_file_to_entries(&path, base_path, entries);
-        }
+    let mut report = Report::default();
+    let entries = pack::collect_entries(files, &PackOptions::default(), &is_newer, &mut report.warnings);
+    if !entries.is_empty() {
+        append_entries(tarfile, &entries).map_err(at(tarfile))?;
     }
+    report.entries = entries.into_iter().map(|e| e.header.name).collect();
+    Ok(report)
 }
 
-// ----------------------------------------------------------------
-// simple methods for reading and writing tar archives
-// ----------------------------------------------------------------
-/// Packs files into a tar archive (supports .tar and .tar.gz)
-pub fn pack(tarfile: &str, files: &[&str]) {
-    let mut entries = Vec::new();
-    
-    for file_path in files {
-        let path = Path::new(file_path);
-        if !path.exists() {
-            eprintln!("Warning: File not found: {}", file_path);
-            continue;
+/// Rejects new archive names that imply compression, since those cannot be appended to
+/// Existing archives are checked by content in `append_entries`
+fn check_appendable(tarfile: &str) -> Result<(), std::io::Error> {
+    if !Path::new(tarfile).exists() && Compression::from_path(tarfile) != Compression::None {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("cannot append to a compressed archive: {}", tarfile),
+        ));
+    }
+    Ok(())
+}
+
+/// Writes entries over the end-of-archive marker of an uncompressed archive
+fn append_entries(tarfile: &str, entries: &[TarEntry]) -> Result<(), std::io::Error> {
+    let mut file = fs::OpenOptions::new().read(true).write(true).create(true).truncate(false).open(tarfile)?;
+
+    let mut head = [0u8; 512];
+    let n = file.read(&mut head)?;
+    if Compression::detect(&head[..n]) != Compression::None {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("cannot append to a compressed archive: {}", tarfile),
+        ));
+    }
+
+    let end = tar::find_end_of_archive(&mut file)?;
+    file.seek(io::SeekFrom::Start(end))?;
+    let mut writer = io::BufWriter::new(&file);
+    write_tar_to(entries, &mut writer)?;
+    writer.flush()?;
+    drop(writer);
+    file.sync_all()
+}
+
+/// Deletes entries from an uncompressed tar archive in place (like `tar --delete`)
+///
+/// The archive is streamed once: kept entries are moved forward over the
+/// removed ones, then a new end-of-archive marker is written and the file
+/// is truncated. Only one buffer of data is held in memory.
+/// A name also matches everything below it when it is a directory.
+/// The removed entries are listed in `Report::entries`.
+pub fn delete(tarfile: &str, names: &[&str]) -> Result<Report, TarError> {
+    delete_entries(tarfile, names).map_err(at(tarfile))
+}
+
+fn delete_entries(tarfile: &str, names: &[&str]) -> Result<Report, std::io::Error> {
+    use std::os::unix::fs::FileExt;
+
+    let file = fs::OpenOptions::new().read(true).write(true).open(tarfile)?;
+    let len = file.metadata()?.len();
+    let mut block = [0u8; 512];
+    let n = file.read_at(&mut block, 0)?;
+    if Compression::detect(&block[..n]) != Compression::None {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "cannot delete from a compressed archive",
+        ));
+    }
+
+    let matches = |name: &str| {
+        names.iter().any(|n| {
+            let dir = n.trim_end_matches('/');
+            name == *n || name.trim_end_matches('/') == dir || name.starts_with(&format!("{}/", dir))
+        })
+    };
+
+    let mut read_pos = 0u64;
+    let mut write_pos = 0u64;
+    let mut report = Report::default();
+    let mut buf = vec![0u8; 64 * 1024];
+    while read_pos + 512 <= len {
+        file.read_exact_at(&mut block, read_pos)?;
+        if block.iter().all(|&b| b == 0) {
+            break;
         }
-        
-        // Check if it's a directory
-        if path.is_dir() {
-            // Recursively add all files in the directory
-            collect_files_from_dir(path, path, &mut entries);
+        let header = TarHeader::from_bytes(&block);
+        let entry_len = 512 + header.size.div_ceil(512) * 512;
+        if read_pos + entry_len > len {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("truncated archive: entry {} runs past the end of the file", header.name),
+            ));
+        }
+        if matches(&header.name) {
+            report.entries.push(header.name);
         } else {
-            // Add single file - use parent directory as base to preserve filename
-            let base = path.parent().unwrap_or_else(|| Path::new(""));
-            add_file_to_entries(path, base, &mut entries);
+            // move the entry forward over removed entries
+            if write_pos != read_pos {
+                let mut done = 0u64;
+                while done < entry_len {
+                    let chunk = (entry_len - done).min(buf.len() as u64) as usize;
+                    file.read_exact_at(&mut buf[..chunk], read_pos + done)?;
+                    file.write_all_at(&buf[..chunk], write_pos + done)?;
+                    done += chunk as u64;
+                }
+            }
+            write_pos += entry_len;
         }
+        read_pos += entry_len;
     }
-    
-    let tar_data = write_tar(&entries);
-    
-    // Compress if needed
-    let result = gzip(tarfile, tar_data)
-        .and_then(|data| fs::write(tarfile, data));
-    
-    match result {
-        Ok(_) => println!("Created tar archive: {}", tarfile),
-        Err(e) => {
-            eprintln!("Error writing tar file: {}", e);
-            std::process::exit(1);
-        }
+
+    if report.count() > 0 {
+        file.write_all_at(&[0u8; 1024], write_pos)?;
+        file.set_len(write_pos + 1024)?;
+        file.sync_all()?;
     }
+    Ok(report)
+}
+
+/// Answer to the question whether an existing file should be overwritten
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overwrite {
+    /// Overwrite this file
+    Yes,
+    /// Keep the existing file and skip the entry
+    No,
+    /// Overwrite this and all following files without asking again
+    All,
 }
 
-/// Unpacks files from a tar archive (supports .tar and .tar.gz)
-pub fn unpack(tarfile: &str, output_dir: &str) {
-    unpack_with_options(tarfile, output_dir, false, true);
+/// Unpacks files from a tar archive
+/// The compression is detected from the file content
+/// Existing files are kept and listed in `Report::skipped`
+/// Absolute entry names and names containing `..` are skipped with a warning
+pub fn unpack(tarfile: &str, output_dir: &str) -> Result<Report, TarError> {
+    unpack_with_options(tarfile, output_dir, false)
 }
 
 /// Unpacks a tar archive with options
@@ -322,131 +408,271 @@ pub fn unpack(tarfile: &str, output_dir: &str) {
 /// # Arguments
 /// * `tarfile` - Path to the tar archive
 /// * `output_dir` - Output directory
-/// * `overwrite` - If true, overwrite existing files without prompting
-///                 If false, skip existing files
-/// * `use_prompt` - If true, prompt user for each existing file
-pub fn unpack_with_options(tarfile: &str, output_dir: &str, overwrite: bool, use_prompt: bool) {
-    let mut overwrite = overwrite;
-    // Read file
-    let file_data = match fs::read(tarfile) {
-        Ok(d) => d,
-        Err(e) => {
-            eprintln!("Error reading tar file: {}", e);
-            std::process::exit(1);
-        }
-    };
-    
-    // Decompress if gzipped
-    let tar_data = match ungzip(tarfile, file_data) {
-        Ok(data) => data,
-        Err(e) => {
-            eprintln!("Error decompressing gzip: {}", e);
-            std::process::exit(1);
-        }
-    };
-    
-    let entries = read_tar(&tar_data);
+/// * `overwrite` - If true, overwrite existing files
+///   If false, skip existing files
+///
+/// The archive is decompressed as a stream while the files are written.
+/// Files that cannot be written are reported in `Report::warnings`; an
+/// archive that cannot be read is an error.
+pub fn unpack_with_options(tarfile: &str, output_dir: &str, overwrite: bool) -> Result<Report, TarError> {
+    let answer = if overwrite { Overwrite::Yes } else { Overwrite::No };
+    unpack_with_prompt(tarfile, output_dir, |_| answer)
+}
+
+/// Unpacks a tar archive, asking `prompt` what to do with each existing file
+///
+/// `prompt` receives the entry name. After `Overwrite::All` it is not
+/// called again and all following files are overwritten.
+pub fn unpack_with_prompt<F>(tarfile: &str, output_dir: &str, prompt: F) -> Result<Report, TarError>
+where
+    F: FnMut(&str) -> Overwrite,
+{
+    unpack_with_progress(tarfile, output_dir, prompt, |_, _| {})
+}
+
+/// Unpacks a tar archive like `unpack_with_prompt`, calling `progress` for
+/// each extracted entry
+///
+/// `progress` receives the entry name and whether an existing file was
+/// replaced, as soon as the entry is written.
+pub fn unpack_with_progress<F, P>(tarfile: &str, output_dir: &str, mut prompt: F, mut progress: P) -> Result<Report, TarError>
+where
+    F: FnMut(&str) -> Overwrite,
+    P: FnMut(&str, bool),
+{
+    // Stream the file through the decoder of the detected format
+    let decoder = compression::open_decoder(tarfile, &CompressOptions::default()).map_err(at(tarfile))?;
+    let mut reader = TarReader::new(decoder);
     
     let output_path = Path::new(output_dir);
     if !output_path.exists() {
-        if let Err(e) = fs::create_dir_all(output_path) {
-            eprintln!("Error creating output directory: {}", e);
-            std::process::exit(1);
-        }
+        fs::create_dir_all(output_path).map_err(at(output_path))?;
     }
     
-    for entry in entries {
-        let file_path = output_path.join(&entry.header.name);
-        let mut flag_overwrite = false;
-        // Check if file exists and overwrite is false
-        if file_path.exists() {
-            if !overwrite {
-                if use_prompt {
-                    // ask to user
-                    println!("❓File '{}' already exists. Overwrite? ([Y]es/[N]o/[A]ll): ", entry.header.name);
-                    let stdin = io::stdin();
-                    let mut line = String::new();
-                    stdin.lock().read_line(&mut line).unwrap_or(0);
-                    let answer = line.trim().to_lowercase();
-                    
-                    if answer == "a" || answer == "all" {
-                        // Overwrite this and all subsequent files
-                        println!("⚡ Overwriting all files...");
-                        overwrite = true;
-                    } else if answer == "y" || answer == "yes" {
-                    } else {
-                        println!("- Skipping: {}", entry.header.name);
-                        continue;
-                    }
-                } else {
-                    println!("- Skipping: {}", entry.header.name);
-                    continue;
+    let mut report = Report::default();
+    let mut overwrite_all = false;
+    // Ask about an existing file; true to replace it
+    let mut confirm = |name: &str, report: &mut Report| {
+        if !overwrite_all {
+            match prompt(name) {
+                Overwrite::Yes => {}
+                Overwrite::All => overwrite_all = true,
+                Overwrite::No => {
+                    report.skipped.push(name.to_string());
+                    return false;
                 }
             }
-            flag_overwrite = true;
+        }
+        true
+    };
+    // Record an extracted entry and report it right away
+    let mut extracted = |name: String, overwritten: bool, report: &mut Report| {
+        progress(&name, overwritten);
+        if overwritten {
+            report.overwritten.push(name.clone());
+        }
+        report.entries.push(name);
+    };
+    // mode and mtime are set once the files inside have been written
+    let mut directories = Vec::new();
+    // links are created last, so no entry is written through one of them
+    let mut symlinks = Vec::new();
+    while let Some(header) = reader.next_entry().map_err(at(tarfile))? {
+        if !is_safe_entry_name(&header.name) {
+            report.warnings.push(format!("Skipping unsafe path {}", header.name));
+            continue;
+        }
+        if header.is_dir() {
+            // an existing link would have its target chmodded and retimed
+            if let Some(link) = existing_symlink(output_path, Path::new(&header.name)) {
+                report.warnings.push(format!("Skipping {}: {} is a symbolic link", header.name, link.display()));
+                continue;
+            }
+            let dir_path = output_path.join(&header.name);
+            if let Err(e) = fs::create_dir_all(&dir_path) {
+                report.warnings.push(format!("Error creating directory {}: {}", dir_path.display(), e));
+                continue;
+            }
+            directories.push((dir_path, header.mode, header.mtime));
+            extracted(header.name, false, &mut report);
+            continue;
+        }
+        if header.is_symlink() {
+            symlinks.push(header);
+            continue;
+        }
+        // Other than directories and links, only regular files are extracted
+        if !header.is_file() {
+            continue;
+        }
+        if let Some(link) = parent_symlink(output_path, &header.name) {
+            report.warnings.push(format!("Skipping {}: {} is a symbolic link", header.name, link.display()));
+            continue;
+        }
+        let file_path = output_path.join(&header.name);
+        let existing = fs::symlink_metadata(&file_path).ok();
+        // Check if file exists and ask whether to overwrite it
+        if let Some(existing) = &existing {
+            if !confirm(&header.name, &mut report) {
+                continue;
+            }
+            // replace an existing link instead of writing to its target
+            if existing.file_type().is_symlink() && let Err(e) = fs::remove_file(&file_path) {
+                report.warnings.push(format!("Error removing {}: {}", header.name, e));
+                continue;
+            }
         }
         
         // Create parent directories if they don't exist
-        if let Some(parent) = file_path.parent() {
-            if !parent.exists() {
-                if let Err(e) = fs::create_dir_all(parent) {
-                    eprintln!("❌ Error creating directory {}: {}", parent.display(), e);
-                    continue;
-                }
-            }
+        if let Some(parent) = file_path.parent()
+            && !parent.exists()
+            && let Err(e) = fs::create_dir_all(parent)
+        {
+            report.warnings.push(format!("Error creating directory {}: {}", parent.display(), e));
+            continue;
         }
         
         match fs::File::create(&file_path) {
             Ok(mut file) => {
-                if let Err(e) = file.write_all(&entry.data) {
-                    eprintln!("❌ Error writing {}: {}", entry.header.name, e);
+                if let Err(e) = io::copy(&mut reader, &mut file) {
+                    report.warnings.push(format!("Error writing {}: {}", header.name, e));
                 } else {
-                    let overwrite_msg = if flag_overwrite { " (overwritten)" } else { "" };
-                    println!("- Extracted: {}{}", entry.header.name, overwrite_msg);
+                    extracted(header.name, existing.is_some(), &mut report);
                 }
             }
             Err(e) => {
-                eprintln!("❌ Error creating {}: {}", entry.header.name, e);
+                report.warnings.push(format!("Error creating {}: {}", header.name, e));
             }
         }
     }
-    
-    println!("Extraction complete to: {}", output_dir);
+
+    for header in symlinks {
+        if let Some(link) = parent_symlink(output_path, &header.name) {
+            report.warnings.push(format!("Skipping {}: {} is a symbolic link", header.name, link.display()));
+            continue;
+        }
+        let link_path = output_path.join(&header.name);
+        let existing = fs::symlink_metadata(&link_path).is_ok();
+        if existing {
+            if !confirm(&header.name, &mut report) {
+                continue;
+            }
+            if let Err(e) = fs::remove_file(&link_path) {
+                report.warnings.push(format!("Error removing {}: {}", header.name, e));
+                continue;
+            }
+        }
+        if let Some(parent) = link_path.parent()
+            && let Err(e) = fs::create_dir_all(parent)
+        {
+            report.warnings.push(format!("Error creating directory {}: {}", parent.display(), e));
+            continue;
+        }
+        match std::os::unix::fs::symlink(&header.linkname, &link_path) {
+            Ok(()) => extracted(header.name, existing, &mut report),
+            Err(e) => report.warnings.push(format!("Error creating link {}: {}", header.name, e)),
+        }
+    }
+
+    // deepest first, so a read-only parent does not block its children
+    for (dir_path, mode, mtime) in directories.into_iter().rev() {
+        if let Err(e) = set_dir_metadata(&dir_path, mode, mtime) {
+            report.warnings.push(format!("Error setting metadata of {}: {}", dir_path.display(), e));
+        }
+    }
+    Ok(report)
 }
 
-/// Lists TarHeader in a tar archive (supports .tar and .tar.gz)
-pub fn list(tarfile: &str) -> Result<Vec<TarHeader>, std::io::Error> {
-    let file_data = fs::read(tarfile)?;
-    
-    // Decompress if gzipped
-    let tar_data = ungzip(tarfile, file_data)?;
-    
-    let entries = read_tar(&tar_data);
-    let headers: Vec<TarHeader> = entries.into_iter().map(|e| e.header).collect();
+/// Whether an entry name stays inside the output directory
+/// Absolute names and names with `..` components are rejected
+fn is_safe_entry_name(name: &str) -> bool {
+    Path::new(name).components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
+
+/// First existing symbolic link on `path` below `output_path`, if any
+/// A link left by an earlier extraction could otherwise redirect writes
+/// outside the output directory
+fn existing_symlink(output_path: &Path, path: &Path) -> Option<std::path::PathBuf> {
+    let mut current = output_path.to_path_buf();
+    for component in path.components() {
+        current.push(component);
+        match fs::symlink_metadata(&current) {
+            Ok(meta) if meta.file_type().is_symlink() => return Some(current),
+            Ok(_) => {}
+            Err(_) => return None,
+        }
+    }
+    None
+}
+
+/// Like `existing_symlink`, for the directories containing the entry `name`
+fn parent_symlink(output_path: &Path, name: &str) -> Option<std::path::PathBuf> {
+    existing_symlink(output_path, Path::new(name).parent()?)
+}
+
+/// Restores the permission bits and modification time of a directory
+fn set_dir_metadata(dir_path: &Path, mode: u32, mtime: u64) -> Result<(), std::io::Error> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let modified = std::time::UNIX_EPOCH + std::time::Duration::from_secs(mtime);
+    fs::File::open(dir_path)?.set_modified(modified)?;
+    fs::set_permissions(dir_path, fs::Permissions::from_mode(mode & 0o7777))
+}
+
+/// Lists TarHeader in a tar archive, including directories and links
+/// The compression is detected from the file content, and the archive is
+/// streamed, so memory use does not grow with its size
+pub fn list(tarfile: &str) -> Result<Vec<TarHeader>, TarError> {
+    list_headers(tarfile).map_err(at(tarfile))
+}
+
+fn list_headers(tarfile: &str) -> Result<Vec<TarHeader>, std::io::Error> {
+    // Stream the archive; entry data is skipped without being kept
+    let mut reader = TarReader::new(compression::open_decoder(tarfile, &CompressOptions::default())?);
+    let mut headers = Vec::new();
+    while let Some(header) = reader.next_entry()? {
+        headers.push(header);
+    }
     Ok(headers)
 }
 
-/// Lists TarEntry in a tar archive (supports .tar and .tar.gz)
-pub fn list_entry(tarfile: &str) -> Result<Vec<TarEntry>, std::io::Error> {
-    let file_data = fs::read(tarfile)?;
-    
-    // Check if input is gzipped
-    let is_gzipped = tarfile.ends_with(".tar.gz") || tarfile.ends_with(".tgz");
-    
-    let tar_data = if is_gzipped {
-        // Decompress with gzip
-        let mut decoder = GzDecoder::new(&file_data[..]);
-        let mut decompressed = Vec::new();
-        decoder.read_to_end(&mut decompressed)?;
-        decompressed
-    } else {
-        file_data
-    };
-    
-    let entries = read_tar(&tar_data);
+/// Lists TarEntry in a tar archive, including directories and links
+/// The compression is detected from the file content
+pub fn list_entry(tarfile: &str) -> Result<Vec<TarEntry>, TarError> {
+    list_entries(tarfile).map_err(at(tarfile))
+}
+
+fn list_entries(tarfile: &str) -> Result<Vec<TarEntry>, std::io::Error> {
+    let mut reader = TarReader::new(compression::open_decoder(tarfile, &CompressOptions::default())?);
+    let mut entries = Vec::new();
+    while let Some(header) = reader.next_entry()? {
+        // not pre-sized: the size comes from the header and may be bogus
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        entries.push(TarEntry { header_bytes: *reader.header_bytes(), header, data: data.into() });
+    }
     Ok(entries)
 }
 
+/// Merges two archives into a new archive (supports .tar and .tar.gz)
+///
+/// Entries of `overlay` are merged into `base` with `Tar::merge`, and the
+/// result is written to `output`.
+pub fn merge(output: &str, base: &str, overlay: &str, policy: MergePolicy) -> Result<MergeReport, TarError> {
+    let mut base_tar = Tar::open(base).map_err(at(base))?;
+    let overlay_tar = Tar::open(overlay).map_err(at(overlay))?;
+    let report = base_tar.merge(&overlay_tar, policy).map_err(at(overlay))?;
+    base_tar.save(output).map_err(at(output))?;
+    Ok(report)
+}
+
+/// Compares two archive files (supports .tar and .tar.gz)
+pub fn diff_files(old: &str, new: &str, options: &DiffOptions) -> Result<TarDiff, TarError> {
+    let old_tar = Tar::open(old).map_err(at(old))?;
+    let new_tar = Tar::open(new).map_err(at(new))?;
+    diff_with_options(&old_tar, &new_tar, options).map_err(at(new))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -465,7 +691,7 @@ mod tests {
         
         // Execute pack function
         let files = vec![test_file1, test_file2];
-        pack(test_tar, &files);
+        pack(test_tar, &files).unwrap();
         
         // Verify tar file was created
         assert!(Path::new(test_tar).exists());
@@ -495,10 +721,10 @@ mod tests {
         
         // Create tar archive
         let files = vec![test_file];
-        pack(test_tar, &files);
+        pack(test_tar, &files).unwrap();
         
         // Execute unpack function
-        unpack_with_options(test_tar, output_dir, false, false);
+        unpack_with_options(test_tar, output_dir, false).unwrap();
         
         // Verify file was extracted
         let extracted_file = Path::new(output_dir).join(test_file);
@@ -526,7 +752,7 @@ mod tests {
         
         // Create tar archive
         let files = vec![test_file1, test_file2];
-        pack(test_tar, &files);
+        pack(test_tar, &files).unwrap();
         
         // Execute list function
         let headers = list(test_tar).unwrap();
@@ -566,7 +792,7 @@ mod tests {
         
         // Execute pack function (.tar.gz format)
         let files = vec![test_file1, test_file2];
-        pack(test_tar_gz, &files);
+        pack(test_tar_gz, &files).unwrap();
         
         // Verify .tar.gz file was created
         assert!(Path::new(test_tar_gz).exists());
@@ -580,7 +806,7 @@ mod tests {
         assert_eq!(headers[1].size, 24);
         
         // Execute unpack function (extract from .tar.gz)
-        unpack_with_options(test_tar_gz, output_dir, false, false);
+        unpack_with_options(test_tar_gz, output_dir, false).unwrap();
         
         // Verify files were extracted
         let extracted_file1 = Path::new(output_dir).join(test_file1);
@@ -614,7 +840,7 @@ mod tests {
         
         // Pack directory
         let files = vec![test_dir];
-        pack(test_tar, &files);
+        pack(test_tar, &files).unwrap();
         
         // Verify tar file was created
         assert!(Path::new(test_tar).exists());
@@ -622,10 +848,11 @@ mod tests {
         // Verify tar file contents
         let tar_data = fs::read(test_tar).unwrap();
         let entries = read_tar(&tar_data);
-        assert_eq!(entries.len(), 3);
+        assert_eq!(entries.len(), 4);
         
         // Verify file names (should be stored as relative paths)
         let names: Vec<String> = entries.iter().map(|e| e.header.name.clone()).collect();
+        assert!(names.contains(&"subdir/".to_string()));
         assert!(names.contains(&"file1.txt".to_string()));
         assert!(names.contains(&"file2.txt".to_string()));
         assert!(names.contains(&"subdir/file3.txt".to_string()));
@@ -650,10 +877,10 @@ mod tests {
         
         // Pack directory
         let files = vec![test_dir];
-        pack(test_tar, &files);
+        pack(test_tar, &files).unwrap();
         
         // unpack
-        unpack_with_options(test_tar, output_dir, false, false);
+        unpack_with_options(test_tar, output_dir, false).unwrap();
         
         // Verify all files were extracted
         assert!(Path::new(output_dir).join("root.txt").exists());
@@ -671,6 +898,45 @@ mod tests {
         fs::remove_dir_all(output_dir).unwrap();
     }
 
+    #[test]
+    fn test_pack_and_unpack_empty_directory() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let test_dir = "test_dir_empty";
+        let test_tar = "test_dir_empty.tar";
+        let output_dir = "test_dir_empty_output";
+        fs::create_dir_all(format!("{}/data/empty", test_dir)).unwrap();
+        fs::write(format!("{}/data/file.txt", test_dir), "File").unwrap();
+        fs::set_permissions(format!("{}/data/empty", test_dir), fs::Permissions::from_mode(0o700)).unwrap();
+        let mtime = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000_000);
+        fs::File::open(format!("{}/data/empty", test_dir)).unwrap().set_modified(mtime).unwrap();
+
+        let mut names = pack(test_tar, &[test_dir]).unwrap().entries;
+        names.sort();
+        assert_eq!(names, vec!["data/", "data/empty/", "data/file.txt"]);
+        let mut reader = TarReader::new(fs::File::open(test_tar).unwrap());
+        while let Some(header) = reader.next_entry().unwrap() {
+            if header.name == "data/empty/" {
+                assert_eq!(header.typeflag, tar::TYPE_DIRECTORY);
+                assert_eq!((header.size, header.mode & 0o7777, header.mtime), (0, 0o700, 1_000_000_000));
+            }
+        }
+
+        // empty directories are recreated with their mode and mtime
+        let report = unpack(test_tar, output_dir).unwrap();
+        assert_eq!(report.count(), 3);
+        let empty = fs::metadata(Path::new(output_dir).join("data/empty")).unwrap();
+        assert!(empty.is_dir());
+        assert_eq!(empty.permissions().mode() & 0o7777, 0o700);
+        assert_eq!(empty.modified().unwrap(), mtime);
+        assert_eq!(fs::read_to_string(Path::new(output_dir).join("data/file.txt")).unwrap(), "File");
+
+        // Cleanup
+        fs::remove_dir_all(test_dir).unwrap();
+        fs::remove_file(test_tar).unwrap();
+        fs::remove_dir_all(output_dir).unwrap();
+    }
+
     #[test]
     fn test_pack_mixed_files_and_directories() {
         // Create test file and directory
@@ -685,15 +951,16 @@ mod tests {
         
         // Pack mixed files and directories
         let files = vec![test_file, test_dir];
-        pack(test_tar, &files);
+        pack(test_tar, &files).unwrap();
         
         // Verify tar file contents
         let tar_data = fs::read(test_tar).unwrap();
         let entries = read_tar(&tar_data);
-        assert_eq!(entries.len(), 3);
+        assert_eq!(entries.len(), 4);
         
         // Verify file names
         let names: Vec<String> = entries.iter().map(|e| e.header.name.clone()).collect();
+        assert!(names.contains(&"subdir/".to_string()));
         assert!(names.contains(&test_file.to_string()));
         assert!(names.contains(&"dir_file.txt".to_string()));
         assert!(names.contains(&"subdir/sub_file.txt".to_string()));
@@ -718,17 +985,18 @@ mod tests {
         
         // Pack directory (gzip compressed)
         let files = vec![test_dir];
-        pack(test_tar_gz, &files);
+        pack(test_tar_gz, &files).unwrap();
         
         // Verify .tar.gz file was created
         assert!(Path::new(test_tar_gz).exists());
         
         // Verify contents with list
         let headers = list(test_tar_gz).unwrap();
-        assert_eq!(headers.len(), 3);
+        assert_eq!(headers.len(), 5);
+        assert_eq!(headers.iter().filter(|h| h.is_dir()).count(), 2);
         
         // Verify by unpacking
-        unpack_with_options(test_tar_gz, output_dir, false, false);
+        unpack_with_options(test_tar_gz, output_dir, false).unwrap();
         assert!(Path::new(output_dir).join("file1.txt").exists());
         assert!(Path::new(output_dir).join("nested/file2.txt").exists());
         assert!(Path::new(output_dir).join("nested/deep/file3.txt").exists());
@@ -745,8 +1013,7 @@ mod tests {
 
     #[test]
     fn security_test_unpack_path_traversal() {
-        // Test that unpacking with path traversal attempts is handled
-        // Note: Current implementation is VULNERABLE - this test documents the risk
+        // Test that entries with `..` components are not extracted
         
         use crate::tar::{TarEntry, TarHeader};
         
@@ -767,15 +1034,18 @@ mod tests {
             let header = TarHeader::new(malicious_path.to_string(), 0o644, 9);
             let data = b"malicious".to_vec();
             let header_bytes = header.to_bytes();
-            entries.push(TarEntry { header, data, header_bytes });
+            entries.push(TarEntry { header, data: data.into(), header_bytes });
         }
         
-        let tar_data = write_tar(&entries);
+        let tar_data = write_tar(&entries).unwrap();
         fs::write(test_tar, tar_data).unwrap();
         
-        // This WILL create files outside the intended directory (VULNERABILITY)
-        // In production, unpack should sanitize paths
-        unpack_with_options(test_tar, output_dir, false, false);
+        let report = unpack_with_options(test_tar, output_dir, false).unwrap();
+        assert!(report.entries.is_empty());
+        assert_eq!(report.warnings.len(), 3);
+        assert!(report.warnings[0].starts_with("Skipping unsafe path ../outside.txt"));
+        assert!(!Path::new("../outside.txt").exists());
+        assert!(!Path::new("outside3.txt").exists());
         
         // Cleanup
         fs::remove_file(test_tar).unwrap();
@@ -791,8 +1061,7 @@ mod tests {
 
     #[test]
     fn security_test_unpack_absolute_path() {
-        // Test handling of absolute paths in tar archives
-        // Note: Current implementation is VULNERABLE
+        // Test that absolute paths in tar archives are not extracted
         
         use crate::tar::{TarEntry, TarHeader};
         
@@ -803,13 +1072,15 @@ mod tests {
         let header = TarHeader::new("/tmp/absolute_file.txt".to_string(), 0o644, 8);
         let data = b"absolute".to_vec();
         let header_bytes = header.to_bytes();
-        let entry = TarEntry { header, data, header_bytes };
+        let entry = TarEntry { header, data: data.into(), header_bytes };
         
-        let tar_data = write_tar(&[entry]);
+        let tar_data = write_tar(&[entry]).unwrap();
         fs::write(test_tar, tar_data).unwrap();
         
-        // This may write to /tmp/absolute_file.txt (VULNERABILITY)
-        unpack_with_options(test_tar, output_dir, false, false);
+        let report = unpack_with_options(test_tar, output_dir, false).unwrap();
+        assert!(report.entries.is_empty());
+        assert_eq!(report.warnings, vec!["Skipping unsafe path /tmp/absolute_file.txt"]);
+        assert!(!Path::new("/tmp/absolute_file.txt").exists());
         
         // Cleanup
         fs::remove_file(test_tar).unwrap();
@@ -820,6 +1091,75 @@ mod tests {
         fs::remove_file("/tmp/absolute_file.txt").ok();
     }
 
+    #[test]
+    fn security_test_unpack_hostile_directory() {
+        // Directory entries must not create or chmod anything outside
+        use std::os::unix::fs::PermissionsExt;
+
+        let test_dir = "test_security_hostile_dir";
+        let output_dir = "test_security_hostile_dir/out";
+        let test_tar = "test_security_hostile_dir/a.tar";
+        let victim = Path::new(test_dir).join("victim");
+        fs::create_dir_all(&victim).unwrap();
+        fs::set_permissions(&victim, fs::Permissions::from_mode(0o755)).unwrap();
+        let absolute = format!("{}/", fs::canonicalize(&victim).unwrap().display());
+
+        let mut tar = Tar::new();
+        for name in ["../victim", "../created", absolute.as_str()] {
+            tar.add_entry(TarEntry::directory(name).with_mode(0o777).with_mtime(0));
+        }
+        tar.add_entry(TarEntry::directory("ok"));
+        fs::write(test_tar, tar.to_bytes().unwrap()).unwrap();
+
+        let report = unpack(test_tar, output_dir).unwrap();
+        assert_eq!(report.entries, vec!["ok/"]);
+        assert_eq!(report.warnings.len(), 3);
+        assert!(report.warnings.iter().all(|w| w.starts_with("Skipping unsafe path ")));
+        let meta = fs::metadata(&victim).unwrap();
+        assert_eq!(meta.permissions().mode() & 0o777, 0o755);
+        assert_ne!(meta.modified().unwrap(), std::time::UNIX_EPOCH);
+        assert!(!Path::new(test_dir).join("created").exists());
+
+        // Cleanup
+        fs::remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn security_test_unpack_through_existing_symlink() {
+        // A link from an earlier extraction must not redirect later entries
+        use std::os::unix::fs::PermissionsExt;
+
+        let test_dir = "test_security_symlink_parent";
+        let output_dir = "test_security_symlink_parent/out";
+        let test_tar = "test_security_symlink_parent/a.tar";
+        let target = Path::new(test_dir).join("target");
+        fs::create_dir_all(&target).unwrap();
+        let target_abs = fs::canonicalize(&target).unwrap();
+
+        let mut tar = Tar::new();
+        tar.add_entry(TarEntry::symlink("evil", target_abs.to_str().unwrap()));
+        fs::write(test_tar, tar.to_bytes().unwrap()).unwrap();
+        let report = unpack(test_tar, output_dir).unwrap();
+        assert_eq!(report.entries, vec!["evil"]);
+
+        let mut tar = Tar::new();
+        tar.add_entry(TarEntry::directory("evil").with_mode(0o700));
+        tar.add_str_entry("evil/x", "x");
+        tar.add_entry(TarEntry::directory("evil/sub"));
+        tar.add_str_entry("evil/sub/y", "y");
+        tar.add_entry(TarEntry::symlink("evil/l", "x"));
+        fs::write(test_tar, tar.to_bytes().unwrap()).unwrap();
+        let report = unpack(test_tar, output_dir).unwrap();
+        assert!(report.entries.is_empty());
+        assert_eq!(report.warnings.len(), 5);
+        assert!(report.warnings.iter().all(|w| w.ends_with("evil is a symbolic link")));
+        assert_eq!(fs::read_dir(&target).unwrap().count(), 0);
+        assert_ne!(fs::metadata(&target).unwrap().permissions().mode() & 0o777, 0o700);
+
+        // Cleanup
+        fs::remove_dir_all(test_dir).unwrap();
+    }
+
     #[test]
     fn security_test_unpack_large_file_size() {
         // Test handling of files with unrealistic size declarations
@@ -833,13 +1173,13 @@ mod tests {
         let header = TarHeader::new("fake_large.txt".to_string(), 0o644, 5);
         let data = b"small".to_vec();
         let header_bytes = header.to_bytes();
-        let entry = TarEntry { header, data, header_bytes };
+        let entry = TarEntry { header, data: data.into(), header_bytes };
         
-        let tar_data = write_tar(&[entry]);
+        let tar_data = write_tar(&[entry]).unwrap();
         fs::write(test_tar, tar_data).unwrap();
         
         // Should handle gracefully
-        unpack_with_options(test_tar, output_dir, false, false);
+        unpack_with_options(test_tar, output_dir, false).unwrap();
         
         // Verify file was created with actual (small) size
         let extracted_file = Path::new(output_dir).join("fake_large.txt");
@@ -868,13 +1208,13 @@ mod tests {
         let header = TarHeader::new("".to_string(), 0o644, 4);
         let data = b"data".to_vec();
         let header_bytes = header.to_bytes();
-        let entry = TarEntry { header, data, header_bytes };
+        let entry = TarEntry { header, data: data.into(), header_bytes };
         
-        let tar_data = write_tar(&[entry]);
+        let tar_data = write_tar(&[entry]).unwrap();
         fs::write(test_tar, tar_data).unwrap();
         
         // Should handle gracefully (may skip or error)
-        unpack_with_options(test_tar, output_dir, false, false);
+        unpack_with_options(test_tar, output_dir, false).unwrap();
         
         // Cleanup
         fs::remove_file(test_tar).unwrap();
@@ -905,14 +1245,14 @@ mod tests {
             let header = TarHeader::new(name.to_string(), 0o644, 7);
             let data = b"special".to_vec();
             let header_bytes = header.to_bytes();
-            entries.push(TarEntry { header, data, header_bytes });
+            entries.push(TarEntry { header, data: data.into(), header_bytes });
         }
         
-        let tar_data = write_tar(&entries);
+        let tar_data = write_tar(&entries).unwrap();
         fs::write(test_tar, tar_data).unwrap();
         
         // Should handle gracefully
-        unpack_with_options(test_tar, output_dir, false, false);
+        unpack_with_options(test_tar, output_dir, false).unwrap();
         
         // Cleanup
         fs::remove_file(test_tar).unwrap();
@@ -944,7 +1284,7 @@ mod tests {
             
             // Pack directory
             let files = vec![test_dir];
-            pack(test_tar, &files);
+            pack(test_tar, &files).unwrap();
             
             // Verify tar was created
             assert!(Path::new(test_tar).exists());
@@ -976,13 +1316,13 @@ mod tests {
         let header = TarHeader::new("important.txt".to_string(), 0o644, 9);
         let data = b"overwrite".to_vec();
         let header_bytes = header.to_bytes();
-        let entry = TarEntry { header, data, header_bytes };
+        let entry = TarEntry { header, data: data.into(), header_bytes };
         
-        let tar_data = write_tar(&[entry]);
+        let tar_data = write_tar(&[entry]).unwrap();
         fs::write(test_tar, tar_data).unwrap();
         
         // Unpack will overwrite existing file
-        unpack_with_options(test_tar, output_dir, true, false);
+        unpack_with_options(test_tar, output_dir, true).unwrap();
         
         // Verify file was overwritten
         let content = fs::read_to_string(&sensitive_file).unwrap();
@@ -992,4 +1332,459 @@ mod tests {
         fs::remove_file(test_tar).unwrap();
         fs::remove_dir_all(output_dir).unwrap();
     }
+
+    #[test]
+    fn test_merge_files() {
+        let base = "test_merge_base.tar";
+        let overlay = "test_merge_overlay.tar.gz";
+        let output = "test_merge_output.tar.gz";
+
+        let mut tar = Tar::new();
+        tar.add_str_entry("shared.txt", "base");
+        tar.add_entry(TarEntry::directory("d"));
+        tar.add_str_entry("d/base.txt", "base only");
+        fs::write(base, tar.to_bytes().unwrap()).unwrap();
+        let mut tar = Tar::new();
+        tar.add_str_entry("shared.txt", "overlay");
+        tar.add_str_entry("overlay.txt", "overlay only");
+        tar.add_entry(TarEntry::symlink("latest", "overlay.txt"));
+        tar.save(overlay).unwrap();
+
+        let report = merge(output, base, overlay, MergePolicy::KeepRight).unwrap();
+        assert_eq!(report.replaced, vec!["shared.txt"]);
+        assert_eq!(report.added, vec!["overlay.txt", "latest"]);
+
+        // directories and links of both inputs are kept
+        let merged = Tar::open(output).unwrap();
+        let names: Vec<&str> = merged.entries.iter().map(|e| e.header.name.as_str()).collect();
+        assert_eq!(names, vec!["shared.txt", "d/", "d/base.txt", "overlay.txt", "latest"]);
+        assert_eq!(merged.entries[0].data, b"overlay");
+        assert!(merged.entries[1].header.is_dir());
+        assert_eq!(merged.entries[4].header.linkname, "overlay.txt");
+
+        assert!(merge(output, base, overlay, MergePolicy::Error).is_err());
+
+        // Cleanup
+        fs::remove_file(base).unwrap();
+        fs::remove_file(overlay).unwrap();
+        fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_diff_files() {
+        let old = "test_diff_old.tar";
+        let new = "test_diff_new.tar.gz";
+
+        let mut tar = Tar::new();
+        tar.add_str_entry("a.txt", "line 1\nline 2\n");
+        fs::write(old, tar.to_bytes().unwrap()).unwrap();
+        tar.set_str("a.txt", "line 1\nline two\n");
+        tar.add_str_entry("b.txt", "new");
+        tar.save(new).unwrap();
+
+        let options = DiffOptions { unified: true, ..Default::default() };
+        let d = diff_files(old, new, &options).unwrap();
+        assert_eq!(d.added, vec!["b.txt"]);
+        assert_eq!(d.content_changed.len(), 1);
+        assert!(d.content_changed[0].unified.as_ref().unwrap().contains("+line two"));
+        // set_str refreshes mtime
+        assert_eq!(d.metadata_changed[0].changes[0].field, "mtime");
+
+        // Cleanup
+        fs::remove_file(old).unwrap();
+        fs::remove_file(new).unwrap();
+    }
+
+    #[test]
+    fn test_diff_files_links_and_directories() {
+        let old = "test_diff_links_old.tar";
+        let new = "test_diff_links_new.tar.gz";
+
+        let mut tar = Tar::new();
+        tar.add_entry(TarEntry::directory("d"));
+        tar.add_str_entry("d/v1.txt", "one");
+        tar.add_str_entry("d/v2.txt", "two");
+        tar.add_entry(TarEntry::symlink("current", "d/v1.txt"));
+        tar.add_entry(TarEntry::file("cfg", Vec::new()));
+        tar.save(old).unwrap();
+        tar.find_entry_mut("current").unwrap().header.linkname = "d/v2.txt".to_string();
+        *tar.find_entry_mut("cfg").unwrap() = TarEntry::symlink("cfg", "d");
+        tar.add_entry(TarEntry::directory("d/sub"));
+        tar.save(new).unwrap();
+
+        let d = diff_files(old, new, &DiffOptions::default()).unwrap();
+        assert_eq!(d.added, vec!["d/sub/"]);
+        assert!(d.removed.is_empty());
+        let changed: Vec<&str> = d.metadata_changed.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(changed, vec!["cfg", "current"]);
+        assert!(d.metadata_changed[0].changes.iter().any(|c| c.field == "type"));
+        let link = &d.metadata_changed[1].changes;
+        assert_eq!(link.len(), 1);
+        assert_eq!((link[0].field, link[0].old.as_str(), link[0].new.as_str()), ("linkname", "d/v1.txt", "d/v2.txt"));
+
+        // Cleanup
+        fs::remove_file(old).unwrap();
+        fs::remove_file(new).unwrap();
+    }
+
+    #[test]
+    fn test_append() {
+        let test_file1 = "test_append_file1.txt";
+        let test_file2 = "test_append_file2.txt";
+        let test_tar = "test_append.tar";
+
+        fs::write(test_file1, "First").unwrap();
+        fs::write(test_file2, "Second").unwrap();
+        fs::remove_file(test_tar).ok();
+
+        // The archive is created on first append
+        assert_eq!(append(test_tar, &[test_file1]).unwrap().count(), 1);
+        let before = fs::read(test_tar).unwrap();
+
+        assert_eq!(append(test_tar, &[test_file2]).unwrap().count(), 1);
+        let after = fs::read(test_tar).unwrap();
+        // Existing entry is untouched
+        assert_eq!(&after[..1024], &before[..1024]);
+
+        let headers = list(test_tar).unwrap();
+        let names: Vec<&str> = headers.iter().map(|h| h.name.as_str()).collect();
+        assert_eq!(names, vec![test_file1, test_file2]);
+
+        // Compressed archives are rejected
+        assert!(append("test_append.tar.gz", &[test_file1]).is_err());
+        assert!(!Path::new("test_append.tar.gz").exists());
+
+        // Cleanup
+        fs::remove_file(test_file1).unwrap();
+        fs::remove_file(test_file2).unwrap();
+        fs::remove_file(test_tar).unwrap();
+    }
+
+    #[test]
+    fn test_delete() {
+        let test_tar = "test_delete.tar";
+        let mut tar = Tar::new();
+        tar.add_str_entry("keep1.txt", "keep 1");
+        tar.add_entry(TarEntry::file("big.bin", vec![9u8; 200 * 1024]));
+        tar.add_str_entry("dir/a.txt", "a");
+        tar.add_str_entry("dir/b.txt", "b");
+        tar.add_str_entry("keep2.txt", &"k".repeat(1000));
+        tar.add_str_entry("big.bin", "second copy");
+        fs::write(test_tar, tar.to_bytes().unwrap()).unwrap();
+
+        // every occurrence of a name and everything below a directory is removed
+        assert_eq!(delete(test_tar, &["big.bin", "dir"]).unwrap().count(), 4);
+        let entries = read_tar(&fs::read(test_tar).unwrap());
+        let names: Vec<&str> = entries.iter().map(|e| e.header.name.as_str()).collect();
+        assert_eq!(names, vec!["keep1.txt", "keep2.txt"]);
+        assert_eq!(entries[1].data, "k".repeat(1000).as_bytes());
+        assert_eq!(fs::metadata(test_tar).unwrap().len(), 512 * 2 + 512 * 3 + 1024);
+
+        // nothing to delete leaves the file untouched
+        assert_eq!(delete(test_tar, &["missing.txt"]).unwrap().count(), 0);
+
+        // compressed archives are rejected
+        let test_tar_gz = "test_delete.tar.gz";
+        tar.save(test_tar_gz).unwrap();
+        assert!(delete(test_tar_gz, &["keep1.txt"]).is_err());
+
+        // Cleanup
+        fs::remove_file(test_tar).unwrap();
+        fs::remove_file(test_tar_gz).unwrap();
+    }
+
+    #[test]
+    fn test_update() {
+        let test_dir = "test_update_dir";
+        let test_tar = "test_update.tar";
+        fs::create_dir_all(test_dir).unwrap();
+        fs::write(format!("{}/same.txt", test_dir), "same").unwrap();
+        fs::write(format!("{}/changed.txt", test_dir), "old").unwrap();
+        fs::create_dir_all(format!("{}/sub", test_dir)).unwrap();
+        std::os::unix::fs::symlink("same.txt", format!("{}/link", test_dir)).unwrap();
+        fs::remove_file(test_tar).ok();
+
+        // Everything is new on the first run; directories and links count as archived
+        assert_eq!(update(test_tar, &[test_dir]).unwrap().count(), 4);
+        assert_eq!(update(test_tar, &[test_dir]).unwrap().count(), 0);
+
+        // Touch one file into the future and add another
+        let changed = format!("{}/changed.txt", test_dir);
+        fs::write(&changed, "new").unwrap();
+        let future = std::time::SystemTime::now() + std::time::Duration::from_secs(10);
+        fs::File::options().write(true).open(&changed).unwrap().set_modified(future).unwrap();
+        fs::write(format!("{}/added.txt", test_dir), "added").unwrap();
+
+        assert_eq!(update(test_tar, &[test_dir]).unwrap().count(), 2);
+        let tar = Tar::open(test_tar).unwrap();
+        assert_eq!(tar.entries.len(), 6);
+        assert_eq!(tar.get_str("changed.txt").as_deref(), Some("new"));
+        assert_eq!(tar.find_all("same.txt").len(), 1);
+
+        // Cleanup
+        fs::remove_dir_all(test_dir).unwrap();
+        fs::remove_file(test_tar).unwrap();
+    }
+
+    #[test]
+    fn test_detect_compression_from_content() {
+        let test_gz = "test_detect_backup.gz";
+        let test_tar = "test_detect_misnamed.tar";
+        let test_dir = "test_detect_out";
+        let mut tar = Tar::new();
+        tar.add_str_entry("hello.txt", "hello");
+        tar.save("test_detect_backup.tar.gz").unwrap();
+        fs::rename("test_detect_backup.tar.gz", test_gz).unwrap();
+        fs::copy(test_gz, test_tar).unwrap();
+
+        for file in [test_gz, test_tar] {
+            assert_eq!(list(file).unwrap()[0].name, "hello.txt");
+            assert_eq!(list_entry(file).unwrap()[0].data, b"hello");
+            unpack_with_options(file, test_dir, true).unwrap();
+            assert_eq!(fs::read_to_string(format!("{}/hello.txt", test_dir)).unwrap(), "hello");
+        }
+        // a gzip file named .tar cannot be appended to
+        assert_eq!(append(test_tar, &[]).unwrap_err().kind(), io::ErrorKind::Unsupported);
+
+        // unsupported codecs are reported instead of parsed as tar
+        fs::write(test_tar, b"\x04\x22\x4d\x18").unwrap();
+        assert_eq!(list(test_tar).unwrap_err().kind(), io::ErrorKind::Unsupported);
+
+        // archives are streamed, so a cut-off gzip file fails while listing
+        let mut tar = Tar::new();
+        tar.add_entry(TarEntry::file("big.bin", (0..200_000u32).map(|i| (i * 7 % 251) as u8).collect::<Vec<u8>>()));
+        tar.save("test_detect_backup.tar.gz").unwrap();
+        let gz = fs::read("test_detect_backup.tar.gz").unwrap();
+        fs::write(test_gz, &gz[..gz.len() / 2]).unwrap();
+        fs::remove_file("test_detect_backup.tar.gz").unwrap();
+        assert!(list(test_gz).is_err());
+
+        // Cleanup
+        fs::remove_dir_all(test_dir).unwrap();
+        fs::remove_file(test_gz).unwrap();
+        fs::remove_file(test_tar).unwrap();
+    }
+
+    #[test]
+    fn test_read_compress_archive() {
+        let test_dir = "test_compress_z_out";
+        let plain = Tar::open("testdata/simple.tar").unwrap();
+        let tar = Tar::open("testdata/simple.tar.Z").unwrap();
+        assert_eq!(tar.entries.len(), plain.entries.len());
+        assert_eq!(tar.get_str("hello.txt"), plain.get_str("hello.txt"));
+
+        let names: Vec<String> = list("testdata/simple.tar.Z").unwrap().into_iter().map(|h| h.name).collect();
+        assert_eq!(names, vec!["hello.txt", "numbers.txt"]);
+        unpack_with_options("testdata/simple.tar.Z", test_dir, true).unwrap();
+        assert_eq!(fs::read(format!("{}/numbers.txt", test_dir)).unwrap(), plain.get_bytes("numbers.txt").unwrap().as_ref());
+
+        // compress archives can be read but not written
+        assert_eq!(tar.save("test_compress_z.tar.Z").unwrap_err().kind(), io::ErrorKind::Unsupported);
+        assert!(!Path::new("test_compress_z.tar.Z").exists());
+
+        // Cleanup
+        fs::remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn test_unpack_report() {
+        let test_dir = "test_unpack_report";
+        let output_dir = "test_unpack_report/out";
+        let test_tar = "test_unpack_report/a.tar";
+        fs::create_dir_all(test_dir).unwrap();
+        fs::write("test_unpack_report/a.txt", "a").unwrap();
+        fs::write("test_unpack_report/b.txt", "b").unwrap();
+        let files = ["test_unpack_report/a.txt", "test_unpack_report/b.txt", "test_unpack_report/missing.txt"];
+        let report = pack(test_tar, &files).unwrap();
+        assert_eq!(report.entries, vec!["a.txt", "b.txt"]);
+        assert_eq!(report.warnings, vec!["File not found: test_unpack_report/missing.txt"]);
+
+        let report = unpack(test_tar, output_dir).unwrap();
+        assert_eq!(report.count(), 2);
+        assert!(report.overwritten.is_empty());
+
+        // existing files are skipped by default
+        fs::write("test_unpack_report/out/a.txt", "old").unwrap();
+        let report = unpack(test_tar, output_dir).unwrap();
+        assert_eq!(report.skipped, vec!["a.txt", "b.txt"]);
+        assert_eq!(fs::read_to_string("test_unpack_report/out/a.txt").unwrap(), "old");
+
+        // the prompt is not asked again after All
+        let mut asked = Vec::new();
+        let report = unpack_with_prompt(test_tar, output_dir, |name| {
+            asked.push(name.to_string());
+            Overwrite::All
+        }).unwrap();
+        assert_eq!(asked, vec!["a.txt"]);
+        assert_eq!(report.overwritten, vec!["a.txt", "b.txt"]);
+        assert_eq!(fs::read_to_string("test_unpack_report/out/a.txt").unwrap(), "a");
+
+        let report = unpack_with_prompt(test_tar, output_dir, |name| {
+            if name == "a.txt" { Overwrite::No } else { Overwrite::Yes }
+        }).unwrap();
+        assert_eq!(report.entries, vec!["b.txt"]);
+        assert_eq!(report.skipped, vec!["a.txt"]);
+
+        // progress is reported per entry while the archive is read
+        let events = std::cell::RefCell::new(Vec::new());
+        let report = unpack_with_progress(
+            test_tar,
+            output_dir,
+            |name| {
+                events.borrow_mut().push(format!("ask {}", name));
+                if name == "a.txt" { Overwrite::Yes } else { Overwrite::No }
+            },
+            |name, overwritten| events.borrow_mut().push(format!("extracted {} {}", name, overwritten)),
+        ).unwrap();
+        assert_eq!(events.into_inner(), vec!["ask a.txt", "extracted a.txt true", "ask b.txt"]);
+        assert_eq!(report.overwritten, vec!["a.txt"]);
+
+        // errors name the file they concern
+        let err = unpack("test_unpack_report/none.tar", output_dir).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(err.path(), Some(Path::new("test_unpack_report/none.tar")));
+        assert!(err.to_string().starts_with("test_unpack_report/none.tar: "));
+        assert_eq!(io::Error::from(err).kind(), io::ErrorKind::NotFound);
+
+        // Cleanup
+        fs::remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn test_pack_with_options() {
+        let test_dir = "test_pack_with_options";
+        fs::create_dir_all("test_pack_with_options/src/sub").unwrap();
+        fs::create_dir_all("test_pack_with_options/target").unwrap();
+        fs::write("test_pack_with_options/src/a.rs", "a").unwrap();
+        fs::write("test_pack_with_options/src/sub/b.rs", "b").unwrap();
+        fs::write("test_pack_with_options/src/debug.log", "log").unwrap();
+        fs::write("test_pack_with_options/target/out.o", "o").unwrap();
+        std::os::unix::fs::symlink("a.rs", "test_pack_with_options/src/link.rs").unwrap();
+
+        // the codec is taken from the options, not from the suffix
+        let output = "test_pack_with_options/out.bin";
+        let options = PackOptions::new()
+            .compression(Compression::Gzip)
+            .base_dir(test_dir)
+            .exclude("*.log")
+            .exclude("target")
+            .symlinks(SymlinkPolicy::Skip)
+            .reproducible(true);
+        let report = pack_with_options(output, &["src", "target"], &options).unwrap();
+        assert_eq!(report.entries, vec!["src/", "src/a.rs", "src/sub/", "src/sub/b.rs"]);
+        assert_eq!(Compression::detect_file(output).unwrap(), Compression::Gzip);
+        let headers = list(output).unwrap();
+        assert!(headers.iter().all(|h| h.mtime == 0 && h.uid == 0 && h.uname.is_empty()));
+
+        // reproducible archives do not depend on file times
+        let first = fs::read(output).unwrap();
+        let file = fs::File::options().write(true).open("test_pack_with_options/src/a.rs").unwrap();
+        file.set_modified(std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000)).unwrap();
+        pack_with_options(output, &["src", "target"], &options).unwrap();
+        assert_eq!(fs::read(output).unwrap(), first);
+
+        // an excluded directory argument is not walked
+        let options = PackOptions::new().base_dir(test_dir).exclude("src/sub");
+        let report = pack_with_options(output, &["src/sub", "src/a.rs"], &options).unwrap();
+        assert_eq!(report.entries, vec!["src/a.rs"]);
+
+        // without a base directory the names are those of `pack`
+        let options = PackOptions::new().strip_components(1).prefix("lib").symlinks(SymlinkPolicy::Skip);
+        let mut names = pack_with_options(output, &["test_pack_with_options/src"], &options).unwrap().entries;
+        names.sort();
+        assert_eq!(names, vec!["lib/b.rs"]);
+
+        // Cleanup
+        fs::remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn test_pack_symlinks() {
+        let test_dir = "test_pack_symlinks";
+        let test_tar = "test_pack_symlinks.tar";
+        let output_dir = "test_pack_symlinks_output";
+        fs::create_dir_all(format!("{}/data", test_dir)).unwrap();
+        fs::write(format!("{}/data/a.txt", test_dir), "A").unwrap();
+        std::os::unix::fs::symlink("a.txt", format!("{}/data/link.txt", test_dir)).unwrap();
+        std::os::unix::fs::symlink("..", format!("{}/data/up", test_dir)).unwrap();
+
+        // links are stored as links by default
+        pack(test_tar, &[test_dir]).unwrap();
+        let mut reader = TarReader::new(fs::File::open(test_tar).unwrap());
+        let mut links = Vec::new();
+        while let Some(header) = reader.next_entry().unwrap() {
+            if header.is_symlink() {
+                links.push((header.name, header.linkname));
+            }
+        }
+        links.sort();
+        assert_eq!(links, vec![("data/link.txt".to_string(), "a.txt".to_string()), ("data/up".to_string(), "..".to_string())]);
+
+        let report = unpack(test_tar, output_dir).unwrap();
+        assert!(report.warnings.is_empty());
+        let link = Path::new(output_dir).join("data/link.txt");
+        assert_eq!(fs::read_link(&link).unwrap(), Path::new("a.txt"));
+        assert_eq!(fs::read_to_string(&link).unwrap(), "A");
+
+        // following links copies the target and stops at the cycle
+        let options = PackOptions::new().symlinks(SymlinkPolicy::Follow);
+        let report = pack_with_options(test_tar, &[test_dir], &options).unwrap();
+        assert!(report.entries.contains(&"data/up/".to_string()));
+        assert!(!report.entries.iter().any(|name| name.starts_with("data/up/data/")));
+        assert_eq!(report.warnings.len(), 1);
+        assert!(report.warnings[0].starts_with("Skipping directory cycle at"));
+        let headers = list(test_tar).unwrap();
+        let copy = headers.iter().find(|h| h.name == "data/link.txt").unwrap();
+        assert_eq!(copy.size, 1);
+
+        // Cleanup
+        fs::remove_dir_all(test_dir).unwrap();
+        fs::remove_file(test_tar).unwrap();
+        fs::remove_dir_all(output_dir).unwrap();
+    }
+
+    #[test]
+    fn test_pack_long_link_and_directory_names() {
+        let test_dir = "test_pack_long_names";
+        let test_tar = "test_pack_long_names.tar";
+        fs::create_dir_all(format!("{}/{}", test_dir, "a".repeat(99))).unwrap();
+        fs::create_dir_all(format!("{}/{}", test_dir, "b".repeat(100))).unwrap();
+        std::os::unix::fs::symlink("t".repeat(100), format!("{}/fits", test_dir)).unwrap();
+        std::os::unix::fs::symlink("t".repeat(101), format!("{}/too_long", test_dir)).unwrap();
+
+        // names that do not fit their header field are skipped with a warning
+        let report = pack(test_tar, &[test_dir]).unwrap();
+        let mut names = report.entries.clone();
+        names.sort();
+        assert_eq!(names, vec![format!("{}/", "a".repeat(99)), "fits".to_string()]);
+        let mut warnings = report.warnings.clone();
+        warnings.sort();
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0].starts_with("Skipping directory ") && warnings[0].ends_with("name longer than 100 bytes"));
+        assert!(warnings[1].starts_with("Skipping link ") && warnings[1].ends_with("target longer than 100 bytes"));
+        let fits = list(test_tar).unwrap().into_iter().find(|h| h.name == "fits").unwrap();
+        assert_eq!(fits.linkname, "t".repeat(100));
+
+        // Cleanup
+        fs::remove_dir_all(test_dir).unwrap();
+        fs::remove_file(test_tar).unwrap();
+    }
+
+    #[test]
+    fn test_list_entry_oversized_header() {
+        let test_tar = "test_list_entry_oversized.tar";
+        // a header claiming ~8 GiB followed by a few bytes of data
+        let mut header = TarHeader::new("huge.bin".to_string(), 0o644, 0o77777777777);
+        header.checksum = tar::calc_checksum(&header.to_bytes());
+        let mut data = header.to_bytes().to_vec();
+        data.extend_from_slice(&[0u8; 1024]);
+        fs::write(test_tar, &data).unwrap();
+
+        let err = list_entry(test_tar).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(list(test_tar).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+
+        // Cleanup
+        fs::remove_file(test_tar).unwrap();
+    }
 }
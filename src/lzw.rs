@@ -0,0 +1,352 @@
+//! Decoder for the Unix `compress(1)` format (`.Z`, `.taz`)
+//!
+//! The stream starts with the magic bytes 1F 9D and a flag byte holding the
+//! maximum code width (9 to 16 bits) and the block mode flag. Codes are
+//! packed LSB first. The width starts at 9 bits and grows as the table
+//! fills; in block mode code 256 clears the table. The compressor writes
+//! codes in groups of eight and pads the current group whenever the width
+//! changes or the table is cleared, so the decoder skips that padding too.
+
+use std::io::{self, Read};
+
+/// Magic bytes of a compress stream
+pub(crate) const MAGIC: [u8; 2] = [0x1f, 0x9d];
+
+const INIT_BITS: u32 = 9;
+const MAX_BITS: u32 = 16;
+const BLOCK_MODE: u8 = 0x80;
+const CLEAR: u32 = 256;
+
+/// Streaming LZW decoder
+pub(crate) struct LzwDecoder<R: Read> {
+    reader: io::BufReader<R>,
+    header_read: bool,
+    max_bits: u32,
+    block_mode: bool,
+    n_bits: u32,
+    max_code: u32,
+    free: u32,
+    prefix: Vec<u16>,
+    suffix: Vec<u8>,
+    prev: Option<u32>,
+    first_char: u8,
+    bit_buf: u64,
+    bit_count: u32,
+    /// Codes read since the start of the current group
+    group_codes: u32,
+    /// Decoded bytes not returned yet
+    out: Vec<u8>,
+    out_pos: usize,
+    done: bool,
+}
+
+impl<R: Read> LzwDecoder<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        Self {
+            reader: io::BufReader::new(reader),
+            header_read: false,
+            max_bits: MAX_BITS,
+            block_mode: true,
+            n_bits: INIT_BITS,
+            max_code: (1 << INIT_BITS) - 1,
+            free: 256,
+            prefix: vec![0; 1 << MAX_BITS],
+            suffix: vec![0; 1 << MAX_BITS],
+            prev: None,
+            first_char: 0,
+            bit_buf: 0,
+            bit_count: 0,
+            group_codes: 0,
+            out: Vec::new(),
+            out_pos: 0,
+            done: false,
+        }
+    }
+
+    fn read_header(&mut self) -> Result<(), io::Error> {
+        let mut header = [0u8; 3];
+        self.reader.read_exact(&mut header).map_err(|_| invalid("missing header"))?;
+        if header[..2] != MAGIC {
+            return Err(invalid("bad magic"));
+        }
+        self.max_bits = (header[2] & 0x1f) as u32;
+        if !(INIT_BITS..=MAX_BITS).contains(&self.max_bits) {
+            return Err(invalid(&format!("unsupported code width: {} bits", self.max_bits)));
+        }
+        if header[2] & 0x60 != 0 {
+            return Err(invalid("reserved flag bits set"));
+        }
+        self.block_mode = header[2] & BLOCK_MODE != 0;
+        self.free = if self.block_mode { CLEAR + 1 } else { CLEAR };
+        for c in 0..256 {
+            self.suffix[c] = c as u8;
+        }
+        self.header_read = true;
+        Ok(())
+    }
+
+    /// Read one byte into the bit buffer; false at end of input
+    fn fill(&mut self) -> Result<bool, io::Error> {
+        let mut byte = [0u8; 1];
+        loop {
+            match self.reader.read(&mut byte) {
+                Ok(0) => return Ok(false),
+                Ok(_) => {
+                    self.bit_buf |= (byte[0] as u64) << self.bit_count;
+                    self.bit_count += 8;
+                    return Ok(true);
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Read the next code; None at end of input
+    fn read_code(&mut self) -> Result<Option<u32>, io::Error> {
+        while self.bit_count < self.n_bits {
+            if !self.fill()? {
+                return Ok(None); // a trailing partial code is padding
+            }
+        }
+        let code = (self.bit_buf & ((1 << self.n_bits) - 1)) as u32;
+        self.bit_buf >>= self.n_bits;
+        self.bit_count -= self.n_bits;
+        self.group_codes += 1;
+        Ok(Some(code))
+    }
+
+    /// Skip the padding up to the end of the current group of eight codes
+    fn skip_to_group_end(&mut self) -> Result<(), io::Error> {
+        let rest = (8 - self.group_codes % 8) % 8;
+        let mut skip = rest * self.n_bits;
+        while skip > 0 {
+            if self.bit_count == 0 && !self.fill()? {
+                break;
+            }
+            let n = skip.min(self.bit_count);
+            self.bit_buf >>= n;
+            self.bit_count -= n;
+            skip -= n;
+        }
+        self.group_codes = 0;
+        Ok(())
+    }
+
+    /// Decode the next code into `out`; false at end of stream
+    fn decode_next(&mut self) -> Result<bool, io::Error> {
+        if !self.header_read {
+            self.read_header()?;
+        }
+        loop {
+            if self.free > self.max_code {
+                self.skip_to_group_end()?;
+                self.n_bits += 1;
+                self.max_code = max_code(self.n_bits, self.max_bits);
+            }
+            let Some(code) = self.read_code()? else {
+                return Ok(false);
+            };
+            if self.block_mode && code == CLEAR {
+                self.skip_to_group_end()?;
+                self.n_bits = INIT_BITS;
+                self.max_code = (1 << INIT_BITS) - 1;
+                self.free = CLEAR + 1;
+                self.prev = None;
+                continue;
+            }
+
+            self.out.clear();
+            self.out_pos = 0;
+            let Some(prev) = self.prev else {
+                if code > 255 {
+                    return Err(invalid("first code is not a literal"));
+                }
+                self.out.push(code as u8);
+                self.first_char = code as u8;
+                self.prev = Some(code);
+                return Ok(true);
+            };
+
+            // KwKwK: the code being defined by this very step
+            let mut c = code;
+            if code >= self.free {
+                if code > self.free {
+                    return Err(invalid(&format!("invalid code {}", code)));
+                }
+                self.out.push(self.first_char);
+                c = prev;
+            }
+            while c > 255 {
+                self.out.push(self.suffix[c as usize]);
+                c = self.prefix[c as usize] as u32;
+            }
+            self.out.push(c as u8);
+            self.first_char = c as u8;
+            self.out.reverse();
+
+            if self.free < 1 << self.max_bits {
+                self.prefix[self.free as usize] = prev as u16;
+                self.suffix[self.free as usize] = self.first_char;
+                self.free += 1;
+            }
+            self.prev = Some(code);
+            return Ok(true);
+        }
+    }
+}
+
+impl<R: Read> Read for LzwDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.out_pos == self.out.len() {
+            if self.done || buf.is_empty() {
+                return Ok(0);
+            }
+            if !self.decode_next()? {
+                self.done = true;
+            }
+        }
+        let n = buf.len().min(self.out.len() - self.out_pos);
+        buf[..n].copy_from_slice(&self.out[self.out_pos..self.out_pos + n]);
+        self.out_pos += n;
+        Ok(n)
+    }
+}
+
+/// Largest code before the width grows; at the maximum width the table
+/// just stops growing. Like gzip and ncompress, 9-bit streams still widen
+/// once, as the initial limit does not depend on the maximum width.
+fn max_code(n_bits: u32, max_bits: u32) -> u32 {
+    if n_bits == max_bits { 1 << max_bits } else { (1 << n_bits) - 1 }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid compress (.Z) data: {}", msg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// Reference compressor following compress(1): codes in groups of
+    /// eight, padded when the width grows or the table is cleared
+    fn compress(data: &[u8], max_bits: u32, block_mode: bool) -> Vec<u8> {
+        struct Out {
+            bytes: Vec<u8>,
+            group: Vec<u8>,
+            bit_buf: u64,
+            bit_count: u32,
+            codes: u32,
+        }
+        impl Out {
+            fn code(&mut self, code: u32, n_bits: u32) {
+                self.bit_buf |= (code as u64) << self.bit_count;
+                self.bit_count += n_bits;
+                while self.bit_count >= 8 {
+                    self.group.push(self.bit_buf as u8);
+                    self.bit_buf >>= 8;
+                    self.bit_count -= 8;
+                }
+                self.codes += 1;
+                if self.codes == 8 {
+                    self.bytes.append(&mut self.group);
+                    self.codes = 0;
+                }
+            }
+            /// Flush the group padded to its full size of n_bits bytes
+            fn pad(&mut self, n_bits: u32) {
+                if self.codes > 0 {
+                    if self.bit_count > 0 {
+                        self.group.push(self.bit_buf as u8);
+                    }
+                    self.group.resize(n_bits as usize, 0);
+                    self.bytes.append(&mut self.group);
+                }
+                self.bit_buf = 0;
+                self.bit_count = 0;
+                self.codes = 0;
+            }
+        }
+
+        let flags = max_bits as u8 | if block_mode { BLOCK_MODE } else { 0 };
+        let mut out = Out { bytes: vec![MAGIC[0], MAGIC[1], flags], group: Vec::new(), bit_buf: 0, bit_count: 0, codes: 0 };
+        let first_free = if block_mode { 257 } else { 256 };
+        let mut table: HashMap<(u32, u8), u32> = HashMap::new();
+        let mut free = first_free;
+        let mut n_bits = INIT_BITS;
+        let mut max = (1 << INIT_BITS) - 1;
+        let Some((&first, rest)) = data.split_first() else {
+            return out.bytes;
+        };
+        let mut ent = first as u32;
+        for &c in rest {
+            if let Some(&code) = table.get(&(ent, c)) {
+                ent = code;
+                continue;
+            }
+            out.code(ent, n_bits);
+            // the width grows once the decoder, one entry behind, needs it
+            if free > max {
+                out.pad(n_bits);
+                n_bits += 1;
+                max = max_code(n_bits, max_bits);
+            }
+            if free < 1 << max_bits {
+                table.insert((ent, c), free);
+                free += 1;
+            } else if block_mode {
+                out.code(CLEAR, n_bits);
+                out.pad(n_bits);
+                table.clear();
+                free = first_free;
+                n_bits = INIT_BITS;
+                max = (1 << INIT_BITS) - 1;
+            }
+            ent = c as u32;
+        }
+        out.code(ent, n_bits);
+        if out.bit_count > 0 {
+            out.group.push(out.bit_buf as u8);
+        }
+        out.bytes.append(&mut out.group);
+        out.bytes
+    }
+
+    fn decompress(data: &[u8]) -> Result<Vec<u8>, io::Error> {
+        let mut out = Vec::new();
+        LzwDecoder::new(data).read_to_end(&mut out)?;
+        Ok(out)
+    }
+
+    /// Deterministic data with both repetition and noise
+    fn sample(len: usize) -> Vec<u8> {
+        let mut x: u32 = 12345;
+        (0..len)
+            .map(|i| {
+                x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                if i % 7 < 4 { b"tar_light "[i % 10] } else { (x >> 16) as u8 }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lzw_round_trip_test() {
+        let data = sample(300_000);
+        for max_bits in [9, 12, 16] {
+            for block_mode in [true, false] {
+                let z = compress(&data, max_bits, block_mode);
+                assert_eq!(decompress(&z).unwrap(), data, "{} bits, block mode {}", max_bits, block_mode);
+            }
+        }
+        assert_eq!(decompress(&compress(b"", 16, true)).unwrap(), b"");
+        assert_eq!(decompress(&compress(b"aaaaaaaaaa", 16, true)).unwrap(), b"aaaaaaaaaa");
+    }
+
+    #[test]
+    fn lzw_invalid_test() {
+        assert!(decompress(b"\x1f\x8b\x90").is_err());
+        assert!(decompress(b"\x1f\x9d\x08").is_err()); // 8-bit codes
+        assert!(decompress(b"\x1f\x9d\x90\xff\xff").is_err()); // first code not a literal
+    }
+}
@@ -1,4 +1,5 @@
-use tar_light::{pack, unpack_with_options, list};
+use tar_light::{pack_with_options, unpack_with_progress, list, append, delete, update, merge, diff_files, ArchiveFormat, CompressOptions, DiffOptions, MergePolicy, Overwrite, PackOptions, Report, SymlinkPolicy};
+use std::io::BufRead;
 use std::env;
 use chrono::{Utc, TimeZone};
 
@@ -14,14 +15,181 @@ fn main() {
     
     match command.as_str() {
         "pack" => {
-            if args.len() < 4 {
+            // Check for compression and packing options
+            let mut options = CompressOptions::default();
+            let mut pack_options = PackOptions::new();
+            let mut arg_idx = 2;
+            while arg_idx < args.len() && args[arg_idx].starts_with("--") {
+                match args[arg_idx].as_str() {
+                    "--level" if arg_idx + 1 < args.len() => {
+                        arg_idx += 1;
+                        options.level = match args[arg_idx].parse() {
+                            Ok(level) => Some(level),
+                            Err(_) => {
+                                eprintln!("Error: Invalid level '{}'", args[arg_idx]);
+                                std::process::exit(1);
+                            }
+                        };
+                    }
+                    "--threads" if arg_idx + 1 < args.len() => {
+                        arg_idx += 1;
+                        options.gzip.threads = match args[arg_idx].parse() {
+                            // 0 uses every available core
+                            Ok(0) => std::thread::available_parallelism().map_or(1, |n| n.get()),
+                            Ok(threads) => threads,
+                            Err(_) => {
+                                eprintln!("Error: Invalid thread count '{}'", args[arg_idx]);
+                                std::process::exit(1);
+                            }
+                        };
+                    }
+                    "--gzip-mtime" if arg_idx + 1 < args.len() => {
+                        arg_idx += 1;
+                        options.gzip.header.mtime = match args[arg_idx].parse() {
+                            Ok(mtime) => Some(mtime),
+                            Err(_) => {
+                                eprintln!("Error: Invalid mtime '{}'", args[arg_idx]);
+                                std::process::exit(1);
+                            }
+                        };
+                    }
+                    "--gzip-name" if arg_idx + 1 < args.len() => {
+                        arg_idx += 1;
+                        options.gzip.header.filename = Some(args[arg_idx].clone());
+                    }
+                    "--gzip-comment" if arg_idx + 1 < args.len() => {
+                        arg_idx += 1;
+                        options.gzip.header.comment = Some(args[arg_idx].clone());
+                    }
+                    "--reproducible" => pack_options = pack_options.reproducible(true),
+                    "--format" if arg_idx + 1 < args.len() => {
+                        arg_idx += 1;
+                        let format = match args[arg_idx].as_str() {
+                            "ustar" => ArchiveFormat::Ustar,
+                            "gnu" => ArchiveFormat::Gnu,
+                            "v7" => ArchiveFormat::V7,
+                            other => {
+                                eprintln!("Error: Unknown format '{}'", other);
+                                std::process::exit(1);
+                            }
+                        };
+                        pack_options = pack_options.format(format);
+                    }
+                    "--exclude" if arg_idx + 1 < args.len() => {
+                        arg_idx += 1;
+                        pack_options = pack_options.exclude(&args[arg_idx]);
+                    }
+                    "--directory" if arg_idx + 1 < args.len() => {
+                        arg_idx += 1;
+                        pack_options = pack_options.base_dir(&args[arg_idx]);
+                    }
+                    "--strip-components" if arg_idx + 1 < args.len() => {
+                        arg_idx += 1;
+                        pack_options = pack_options.strip_components(parse_number(&args[arg_idx], "component count"));
+                    }
+                    "--prefix" if arg_idx + 1 < args.len() => {
+                        arg_idx += 1;
+                        pack_options = pack_options.prefix(&args[arg_idx]);
+                    }
+                    "--owner" if arg_idx + 1 < args.len() => {
+                        arg_idx += 1;
+                        let (uid, name) = parse_id_name(&args[arg_idx], "owner");
+                        pack_options = pack_options.owner(uid, name);
+                    }
+                    "--group" if arg_idx + 1 < args.len() => {
+                        arg_idx += 1;
+                        let (gid, name) = parse_id_name(&args[arg_idx], "group");
+                        pack_options = pack_options.group(gid, name);
+                    }
+                    "--numeric-owner" => pack_options = pack_options.numeric_owner(true),
+                    "--mtime" if arg_idx + 1 < args.len() => {
+                        arg_idx += 1;
+                        pack_options = pack_options.mtime(parse_number(&args[arg_idx], "mtime"));
+                    }
+                    "--skip-symlinks" => pack_options = pack_options.symlinks(SymlinkPolicy::Skip),
+                    "--dereference" => pack_options = pack_options.symlinks(SymlinkPolicy::Follow),
+                    "--indexed" => options.gzip.indexed = true,
+                    "--long" => options.zstd.long_distance = true,
+                    "--seekable" => options.zstd.seekable = true,
+                    opt => {
+                        eprintln!("Error: Unknown option '{}'", opt);
+                        print_usage();
+                        std::process::exit(1);
+                    }
+                }
+                arg_idx += 1;
+            }
+            if args.len() < arg_idx + 2 {
                 eprintln!("Error: pack requires at least tarfile and one input file");
                 print_usage();
                 std::process::exit(1);
             }
+            let tarfile = &args[arg_idx];
+            let files: Vec<&str> = args[arg_idx + 1..].iter().map(|s| s.as_str()).collect();
+            match pack_with_options(tarfile, &files, &pack_options.compress_options(options)) {
+                Ok(report) => {
+                    print_warnings(&report);
+                    println!("Created tar archive: {}", tarfile);
+                }
+                Err(e) => {
+                    eprintln!("Error writing tar file: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        "append" => {
+            if args.len() < 4 {
+                eprintln!("Error: append requires tarfile and at least one input file");
+                print_usage();
+                std::process::exit(1);
+            }
             let tarfile = &args[2];
             let files: Vec<&str> = args[3..].iter().map(|s| s.as_str()).collect();
-            pack(tarfile, &files);
+            match append(tarfile, &files) {
+                Ok(report) => {
+                    print_warnings(&report);
+                    println!("Appended {} file(s) to: {}", report.count(), tarfile);
+                }
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        "update" => {
+            if args.len() < 4 {
+                eprintln!("Error: update requires tarfile and at least one input file");
+                print_usage();
+                std::process::exit(1);
+            }
+            let tarfile = &args[2];
+            let files: Vec<&str> = args[3..].iter().map(|s| s.as_str()).collect();
+            match update(tarfile, &files) {
+                Ok(report) => {
+                    print_warnings(&report);
+                    println!("Appended {} newer file(s) to: {}", report.count(), tarfile);
+                }
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        "delete" => {
+            if args.len() < 4 {
+                eprintln!("Error: delete requires tarfile and at least one entry name");
+                print_usage();
+                std::process::exit(1);
+            }
+            let tarfile = &args[2];
+            let names: Vec<&str> = args[3..].iter().map(|s| s.as_str()).collect();
+            match delete(tarfile, &names) {
+                Ok(report) => println!("Deleted {} entry(s) from: {}", report.count(), tarfile),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
         }
         "unpack" => {
             if args.len() < 4 {
@@ -47,7 +215,28 @@ fn main() {
             
             let tarfile = &args[arg_idx];
             let output_dir = &args[arg_idx + 1];
-            unpack_with_options(tarfile, output_dir, overwrite, true);
+            // entries are printed as they are extracted
+            let prompt = |name: &str| {
+                let answer = if overwrite { Overwrite::Yes } else { ask_overwrite(name) };
+                if answer == Overwrite::No {
+                    println!("- Skipping: {}", name);
+                }
+                answer
+            };
+            let progress = |name: &str, overwritten: bool| {
+                let overwrite_msg = if overwritten { " (overwritten)" } else { "" };
+                println!("- Extracted: {}{}", name, overwrite_msg);
+            };
+            match unpack_with_progress(tarfile, output_dir, prompt, progress) {
+                Ok(report) => {
+                    print_warnings(&report);
+                    println!("Extraction complete to: {}", output_dir);
+                }
+                Err(e) => {
+                    eprintln!("Error reading tar file: {}", e);
+                    std::process::exit(1);
+                }
+            }
         }
         "list" => {
             if args.len() < 3 {
@@ -59,12 +248,16 @@ fn main() {
             match list(tarfile) {
                 Ok(headers) => {
                     println!("Files in {}:", tarfile);
-                    println!("{:>10}  {}", "Size", "Name");
+                    println!("{:>10}  Name", "Size");
                     println!("{}", "-".repeat(50));
                     for header in &headers {
-                        println!("{:>10}  {}", header.size, header.name);
+                        if header.linkname.is_empty() {
+                            println!("{:>10}  {}", header.size, header.name);
+                        } else {
+                            println!("{:>10}  {} -> {}", header.size, header.name, header.linkname);
+                        }
                     }
-                    println!("\nTotal: {} file(s)", headers.len());
+                    println!("\nTotal: {} entry(ies)", headers.len());
                 }
                 Err(e) => {
                     eprintln!("Error: {}", e);
@@ -81,6 +274,83 @@ fn main() {
             let tarfile = &args[2];
             list_detail(tarfile);
         }
+        "merge" => {
+            // Check for --policy option
+            let mut policy = MergePolicy::KeepRight;
+            let mut arg_idx = 2;
+            if args.len() >= 4 && args[2] == "--policy" {
+                policy = match args[3].parse() {
+                    Ok(p) => p,
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1);
+                    }
+                };
+                arg_idx = 4;
+            }
+            if args.len() < arg_idx + 3 {
+                eprintln!("Error: merge requires output, base and overlay tarfiles");
+                print_usage();
+                std::process::exit(1);
+            }
+            let output = &args[arg_idx];
+            let base = &args[arg_idx + 1];
+            let overlay = &args[arg_idx + 2];
+            match merge(output, base, overlay, policy) {
+                Ok(report) => {
+                    for name in &report.added {
+                        println!("- Added: {}", name);
+                    }
+                    for name in &report.replaced {
+                        println!("- Replaced: {}", name);
+                    }
+                    for name in &report.kept {
+                        println!("- Kept: {}", name);
+                    }
+                    println!("Created merged archive: {}", output);
+                }
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        "diff" => {
+            // Check for --json and --text options
+            let mut json = false;
+            let mut options = DiffOptions::default();
+            let mut arg_idx = 2;
+            while arg_idx < args.len() && args[arg_idx].starts_with("--") {
+                match args[arg_idx].as_str() {
+                    "--json" => json = true,
+                    "--text" => options.unified = true,
+                    opt => {
+                        eprintln!("Error: Unknown option '{}'", opt);
+                        print_usage();
+                        std::process::exit(1);
+                    }
+                }
+                arg_idx += 1;
+            }
+            if args.len() < arg_idx + 2 {
+                eprintln!("Error: diff requires two tarfiles");
+                print_usage();
+                std::process::exit(1);
+            }
+            match diff_files(&args[arg_idx], &args[arg_idx + 1], &options) {
+                Ok(d) => {
+                    if json {
+                        println!("{}", d.to_json());
+                    } else {
+                        print!("{}", d);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
         _ => {
             eprintln!("Error: Unknown command '{}'", command);
             print_usage();
@@ -89,12 +359,63 @@ fn main() {
     }
 }
 
+/// Parses a numeric option value or exits with an error
+fn parse_number<T: std::str::FromStr>(value: &str, what: &str) -> T {
+    value.parse().unwrap_or_else(|_| {
+        eprintln!("Error: Invalid {} '{}'", what, value);
+        std::process::exit(1);
+    })
+}
+
+/// Parses an `ID[:NAME]` owner or group value
+fn parse_id_name<'a>(value: &'a str, what: &str) -> (u32, &'a str) {
+    let (id, name) = value.split_once(':').unwrap_or((value, ""));
+    (parse_number(id, what), name)
+}
+
+/// Asks on the terminal whether an existing file should be overwritten
+fn ask_overwrite(name: &str) -> Overwrite {
+    println!("❓File '{}' already exists. Overwrite? ([Y]es/[N]o/[A]ll): ", name);
+    let mut line = String::new();
+    std::io::stdin().lock().read_line(&mut line).unwrap_or(0);
+    match line.trim().to_lowercase().as_str() {
+        "a" | "all" => {
+            println!("⚡ Overwriting all files...");
+            Overwrite::All
+        }
+        "y" | "yes" => Overwrite::Yes,
+        _ => Overwrite::No,
+    }
+}
+
+/// Prints the problems that did not stop an operation
+fn print_warnings(report: &Report) {
+    for warning in &report.warnings {
+        eprintln!("❌ {}", warning);
+    }
+}
+
 fn print_usage() {
     eprintln!("Usage:");
     eprintln!("  pack <tarfile> <file1> <file2> … - Create tar archive");
+    eprintln!("    [--level N] [--long] [--seekable] - Compression level; zstd long mode, seekable frames");
+    eprintln!("    [--threads N] [--indexed]      - gzip on N threads (0: all cores); per-entry members with an index");
+    eprintln!("    [--gzip-mtime N] [--gzip-name NAME] [--gzip-comment TEXT]");
+    eprintln!("                                   - gzip header fields (mtime 0 unless given)");
+    eprintln!("    [--format ustar|gnu|v7] [--exclude PATTERN] [--directory DIR]");
+    eprintln!("    [--dereference] [--skip-symlinks] - Archive what symbolic links point to, or leave them out");
+    eprintln!("    [--strip-components N] [--prefix DIR] [--owner ID[:NAME]] [--group ID[:NAME]]");
+    eprintln!("    [--numeric-owner] [--mtime N]  - Header format, file selection, entry names and owners");
+    eprintln!("    [--reproducible]               - Sort entries and clear times and owners");
+    eprintln!("  append <tarfile> <file1> …       - Append files to an uncompressed tar archive");
+    eprintln!("  update <tarfile> <file1> …       - Append files newer than their archived copies");
+    eprintln!("  delete <tarfile> <name1> …       - Delete entries from an uncompressed tar archive");
     eprintln!("  unpack [-y] <tarfile> <dir>      - Extract tar archive");
     eprintln!("  list <tarfile>                   - List files in tar archive");
     eprintln!("  detail <tarfile>                 - List files with detailed information");
+    eprintln!("  merge [--policy left|right|newer|error] <output> <base> <overlay>");
+    eprintln!("                                   - Merge overlay into base (default: right)");
+    eprintln!("  diff [--json] [--text] <old> <new> - Show differences between archives");
 }
 
 fn list_detail(tarfile: &str) {
@@ -129,7 +450,7 @@ fn list_detail(tarfile: &str) {
                 }
                 println!("{}", "-".repeat(80));
             }
-            println!("\nTotal: {} file(s)", headers.len());
+            println!("\nTotal: {} entry(ies)", headers.len());
         }
         Err(e) => {
             eprintln!("Error: {}", e);
@@ -141,6 +462,7 @@ fn list_detail(tarfile: &str) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use tar_light::{pack, unpack_with_options};
     use std::fs;
     use std::path::Path;
 
@@ -156,7 +478,7 @@ mod tests {
         
         // Execute pack function
         let files = vec![test_file1, test_file2];
-        pack(test_tar, &files);
+        pack(test_tar, &files).unwrap();
         
         // Verify tar file was created
         assert!(Path::new(test_tar).exists());
@@ -179,10 +501,10 @@ mod tests {
         
         // Create tar archive
         let files = vec![test_file];
-        pack(test_tar, &files);
+        pack(test_tar, &files).unwrap();
         
         // Execute unpack function
-        unpack_with_options(test_tar, output_dir, true, false);
+        unpack_with_options(test_tar, output_dir, true).unwrap();
         
         // Verify file was extracted
         let extracted_file = Path::new(output_dir).join(test_file);
@@ -210,7 +532,7 @@ mod tests {
         
         // Create tar archive
         let files = vec![test_file1, test_file2];
-        pack(test_tar, &files);
+        pack(test_tar, &files).unwrap();
         
         // Execute list function
         let headers = list(test_tar).unwrap();
@@ -236,7 +558,7 @@ mod tests {
             "src/tar.rs",
             "Cargo.toml",
         ];
-        pack("a.tar.gz", &files);
+        pack("a.tar.gz", &files).unwrap();
         let headers = list("a.tar.gz").unwrap();
         assert!(headers.len() == 4);
         // cleanup
@@ -0,0 +1,236 @@
+//! Merge two Tar archives with a conflict policy
+//!
+//! # Usage
+//!
+//! ```rust
+//! use tar_light::{MergePolicy, Tar};
+//!
+//! let mut base = Tar::new();
+//! base.add_str_entry("config.txt", "base");
+//! base.add_str_entry("base.txt", "only in base");
+//!
+//! let mut overlay = Tar::new();
+//! overlay.add_str_entry("config.txt", "overlay");
+//! overlay.add_str_entry("extra.txt", "only in overlay");
+//!
+//! let report = base.merge(&overlay, MergePolicy::KeepRight).unwrap();
+//! assert_eq!(report.replaced, vec!["config.txt"]);
+//! assert_eq!(report.added, vec!["extra.txt"]);
+//! assert_eq!(base.get_str("config.txt").as_deref(), Some("overlay"));
+//! ```
+
+use crate::tar::{Tar, TarEntry};
+use std::collections::HashSet;
+use std::io;
+
+/// How to resolve an entry that exists in both archives
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergePolicy {
+    /// Keep the entry of the archive being merged into
+    KeepLeft,
+    /// Replace it with the entry of the other archive
+    KeepRight,
+    /// Keep the entry with the newer mtime (the left one on a tie)
+    NewerWins,
+    /// Fail without modifying the archive
+    Error,
+}
+
+impl std::str::FromStr for MergePolicy {
+    type Err = io::Error;
+
+    /// Parse a policy name: `left`, `right`, `newer` or `error`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "left" | "keep-left" => Ok(MergePolicy::KeepLeft),
+            "right" | "keep-right" => Ok(MergePolicy::KeepRight),
+            "newer" | "newer-mtime" => Ok(MergePolicy::NewerWins),
+            "error" => Ok(MergePolicy::Error),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("unknown merge policy: {}", s))),
+        }
+    }
+}
+
+/// Result of a merge
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MergeReport {
+    /// Names only present in the other archive
+    pub added: Vec<String>,
+    /// Names whose entry was replaced by the other archive's entry
+    pub replaced: Vec<String>,
+    /// Names present in both archives where the left entry was kept
+    pub kept: Vec<String>,
+}
+
+impl Tar {
+    /// Merge the entries of `other` into this archive
+    ///
+    /// Entries are matched by name, using the last occurrence of each name.
+    /// Replaced entries keep their position; added entries are appended in
+    /// the order of `other`. Entries with identical content and headers
+    /// (owner and mtime included) are not treated as conflicts.
+    pub fn merge(&mut self, other: &Tar, policy: MergePolicy) -> Result<MergeReport, io::Error> {
+        // last occurrence of each name in `other`, in archive order
+        let mut seen = HashSet::new();
+        let mut incoming: Vec<&TarEntry> = other
+            .entries
+            .iter()
+            .rev()
+            .filter(|e| seen.insert(e.header.name.as_str()))
+            .collect();
+        incoming.reverse();
+
+        if policy == MergePolicy::Error {
+            let mut conflicts: Vec<&str> = Vec::new();
+            for e in &incoming {
+                if let Some(l) = self.find_entry(&e.header.name)
+                    && !same_entry(l, e)?
+                {
+                    conflicts.push(e.header.name.as_str());
+                }
+            }
+            if !conflicts.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("merge conflict: {}", conflicts.join(", ")),
+                ));
+            }
+        }
+
+        let mut report = MergeReport::default();
+        for right in incoming {
+            let name = &right.header.name;
+            let Some(mut left) = self.find_entry_mut(name) else {
+                report.added.push(name.clone());
+                self.add_entry(right.clone());
+                continue;
+            };
+            if same_entry(&left, right)? {
+                continue;
+            }
+            let take_right = match policy {
+                MergePolicy::KeepLeft => false,
+                MergePolicy::KeepRight | MergePolicy::Error => true,
+                MergePolicy::NewerWins => right.header.mtime > left.header.mtime,
+            };
+            if take_right {
+                *left = right.clone();
+                report.replaced.push(name.clone());
+            } else {
+                report.kept.push(name.clone());
+            }
+        }
+        Ok(report)
+    }
+}
+
+/// Returns true if two entries have the same content and metadata
+/// Every header field counts, including owner and mtime
+fn same_entry(a: &TarEntry, b: &TarEntry) -> Result<bool, io::Error> {
+    Ok(a.header.to_bytes() == b.header.to_bytes() && a.data.content_eq(&b.data)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base_and_overlay() -> (Tar, Tar) {
+        let mut base = Tar::new();
+        base.add_entry(TarEntry::file("a.txt", b"base a".to_vec()).with_mtime(200));
+        base.add_entry(TarEntry::file("b.txt", b"base b".to_vec()).with_mtime(100));
+        base.add_entry(TarEntry::file("same.txt", b"same".to_vec()));
+        let mut overlay = Tar::new();
+        overlay.add_entry(TarEntry::file("a.txt", b"overlay a".to_vec()).with_mtime(100));
+        overlay.add_entry(TarEntry::file("b.txt", b"overlay b".to_vec()).with_mtime(200));
+        overlay.add_entry(TarEntry::file("same.txt", b"same".to_vec()));
+        overlay.add_entry(TarEntry::file("c.txt", b"overlay c".to_vec()));
+        (base, overlay)
+    }
+
+    #[test]
+    fn merge_keep_left_and_right_test() {
+        let (mut base, overlay) = base_and_overlay();
+        let report = base.merge(&overlay, MergePolicy::KeepLeft).unwrap();
+        assert_eq!(report.kept, vec!["a.txt", "b.txt"]);
+        assert_eq!(report.added, vec!["c.txt"]);
+        assert!(report.replaced.is_empty());
+        assert_eq!(base.get_str("a.txt").as_deref(), Some("base a"));
+        assert_eq!(base.entries.len(), 4);
+
+        let (mut base, overlay) = base_and_overlay();
+        let report = base.merge(&overlay, MergePolicy::KeepRight).unwrap();
+        assert_eq!(report.replaced, vec!["a.txt", "b.txt"]);
+        assert_eq!(base.get_str("a.txt").as_deref(), Some("overlay a"));
+        // replaced entries keep their position
+        assert_eq!(base.entries[0].header.name, "a.txt");
+        assert_eq!(base.entries[3].header.name, "c.txt");
+    }
+
+    #[test]
+    fn merge_newer_wins_test() {
+        let (mut base, overlay) = base_and_overlay();
+        let report = base.merge(&overlay, MergePolicy::NewerWins).unwrap();
+        assert_eq!(report.kept, vec!["a.txt"]);
+        assert_eq!(report.replaced, vec!["b.txt"]);
+        assert_eq!(base.get_str("a.txt").as_deref(), Some("base a"));
+        assert_eq!(base.get_str("b.txt").as_deref(), Some("overlay b"));
+    }
+
+    #[test]
+    fn merge_error_policy_test() {
+        let (mut base, overlay) = base_and_overlay();
+        let err = base.merge(&overlay, MergePolicy::Error).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert!(err.to_string().contains("a.txt, b.txt"));
+        // the archive is unchanged
+        assert_eq!(base.entries.len(), 3);
+        assert_eq!(base.get_str("a.txt").as_deref(), Some("base a"));
+
+        // identical entries are not conflicts
+        let mut other = Tar::new();
+        other.add_entry(TarEntry::file("same.txt", b"same".to_vec()));
+        other.add_str_entry("new.txt", "new");
+        let report = base.merge(&other, MergePolicy::Error).unwrap();
+        assert_eq!(report.added, vec!["new.txt"]);
+
+        // a metadata-only change is a conflict
+        for changed in [
+            TarEntry::file("same.txt", b"same".to_vec()).with_mtime(5),
+            TarEntry::file("same.txt", b"same".to_vec()).with_mode(0o600),
+        ] {
+            let mut other = Tar::new();
+            other.add_entry(changed);
+            assert!(base.merge(&other, MergePolicy::Error).is_err());
+        }
+        let mut other = Tar::new();
+        let mut owned = TarEntry::file("same.txt", b"same".to_vec());
+        owned.header.uid = 1000;
+        owned.refresh_header();
+        other.add_entry(owned);
+        let report = base.merge(&other, MergePolicy::KeepRight).unwrap();
+        assert_eq!(report.replaced, vec!["same.txt"]);
+        assert_eq!(base.find_entry("same.txt").unwrap().header.uid, 1000);
+
+        // data that cannot be read for the comparison is an error
+        let path = "test_merge_unreadable.bin";
+        std::fs::write(path, "same").unwrap();
+        let mut other = Tar::new();
+        let mut lazy = TarEntry::file("same.txt", crate::EntryData::from_path(path).unwrap());
+        lazy.header.uid = 1000;
+        lazy.refresh_header();
+        other.add_entry(lazy);
+        std::fs::remove_file(path).unwrap();
+        let err = base.merge(&other, MergePolicy::Error).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(base.merge(&other, MergePolicy::KeepLeft).is_err());
+    }
+
+    #[test]
+    fn merge_policy_from_str_test() {
+        assert_eq!("left".parse::<MergePolicy>().unwrap(), MergePolicy::KeepLeft);
+        assert_eq!("keep-right".parse::<MergePolicy>().unwrap(), MergePolicy::KeepRight);
+        assert_eq!("newer".parse::<MergePolicy>().unwrap(), MergePolicy::NewerWins);
+        assert_eq!("error".parse::<MergePolicy>().unwrap(), MergePolicy::Error);
+        assert!("other".parse::<MergePolicy>().is_err());
+    }
+}
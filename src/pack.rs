@@ -0,0 +1,546 @@
+//! Options for creating archives from files on disk
+//!
+//! `PackOptions` is a builder for `pack_with_options`. Everything left
+//! unset behaves like `pack`.
+//!
+//! ```rust
+//! use tar_light::{pack_with_options, ArchiveFormat, Compression, PackOptions};
+//!
+//! let options = PackOptions::new()
+//!     .compression(Compression::Gzip)
+//!     .level(9)
+//!     .format(ArchiveFormat::Gnu)
+//!     .base_dir("testdata")
+//!     .exclude("*.tar*")
+//!     .prefix("backup")
+//!     .owner(0, "root")
+//!     .group(0, "root")
+//!     .reproducible(true);
+//! let report = pack_with_options("pack_options_doc.tgz", &["file1.txt", "file2.txt"], &options).unwrap();
+//! assert_eq!(report.entries, vec!["backup/file1.txt", "backup/file2.txt"]);
+//! # std::fs::remove_file("pack_options_doc.tgz").unwrap();
+//! ```
+
+use crate::compression::{CompressOptions, Compression};
+use crate::entry_data::EntryData;
+use crate::tar::{glob_match, TarEntry, TarHeader, TYPE_DIRECTORY, TYPE_SYMLINK};
+use crate::{get_groupname_from_gid, get_username_from_uid};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Header layout of the written entries
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    /// POSIX ustar (magic "ustar\0", version "00")
+    #[default]
+    Ustar,
+    /// GNU tar (magic "ustar ", version " \0")
+    Gnu,
+    /// Pre-POSIX tar: no magic and no owner names
+    V7,
+}
+
+/// What to do with symbolic links found while packing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SymlinkPolicy {
+    /// Store the link itself, with its target as the linkname
+    #[default]
+    Preserve,
+    /// Archive the file or directory the link points to (`--dereference`)
+    /// A link back to a directory being packed is reported and skipped
+    Follow,
+    /// Leave symbolic links out of the archive
+    Skip,
+}
+
+/// Function renaming an entry; see `PackOptions::map_name`
+type NameMap = dyn Fn(&str) -> String + Send + Sync;
+
+/// Settings for `pack_with_options`
+#[derive(Clone, Default)]
+pub struct PackOptions {
+    compression: Option<Compression>,
+    compress: CompressOptions,
+    format: ArchiveFormat,
+    symlinks: SymlinkPolicy,
+    exclude: Vec<String>,
+    base_dir: Option<PathBuf>,
+    strip_components: usize,
+    prefix: String,
+    map_name: Option<Arc<NameMap>>,
+    owner: Option<(u32, String)>,
+    group: Option<(u32, String)>,
+    numeric_owner: bool,
+    mtime: Option<u64>,
+    reproducible: bool,
+}
+
+impl fmt::Debug for PackOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PackOptions")
+            .field("compression", &self.compression)
+            .field("compress", &self.compress)
+            .field("format", &self.format)
+            .field("symlinks", &self.symlinks)
+            .field("exclude", &self.exclude)
+            .field("base_dir", &self.base_dir)
+            .field("strip_components", &self.strip_components)
+            .field("prefix", &self.prefix)
+            .field("map_name", &self.map_name.as_ref().map(|_| ".."))
+            .field("owner", &self.owner)
+            .field("group", &self.group)
+            .field("numeric_owner", &self.numeric_owner)
+            .field("mtime", &self.mtime)
+            .field("reproducible", &self.reproducible)
+            .finish()
+    }
+}
+
+impl PackOptions {
+    /// Options that behave like `pack`
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Set the codec instead of choosing it from the file name suffix
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+    /// Set the compression level (see `CompressOptions::level`)
+    pub fn level(mut self, level: i32) -> Self {
+        self.compress.level = Some(level);
+        self
+    }
+    /// Set all compression settings at once
+    pub fn compress_options(mut self, options: CompressOptions) -> Self {
+        self.compress = options;
+        self
+    }
+    /// Set the header layout
+    pub fn format(mut self, format: ArchiveFormat) -> Self {
+        self.format = format;
+        self
+    }
+    /// Set how symbolic links are handled
+    pub fn symlinks(mut self, policy: SymlinkPolicy) -> Self {
+        self.symlinks = policy;
+        self
+    }
+    /// Leave out files and directories matching a glob pattern
+    /// The pattern is matched against the entry name and, if it has no
+    /// '/', against each path component, like `tar --exclude`
+    pub fn exclude(mut self, pattern: &str) -> Self {
+        self.exclude.push(pattern.to_string());
+        self
+    }
+    /// Resolve the input paths relative to `dir` and name the entries by
+    /// their path below it, like `tar -C dir`
+    pub fn base_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.base_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+    /// Remove `n` leading components from entry names
+    /// Entries with no components left are not archived
+    pub fn strip_components(mut self, n: usize) -> Self {
+        self.strip_components = n;
+        self
+    }
+    /// Put every entry below the directory `prefix`
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.trim_end_matches('/').to_string();
+        self
+    }
+    /// Rename entries with `f`, after `strip_components` and before `prefix`
+    /// An empty name leaves the entry out
+    pub fn map_name<F: Fn(&str) -> String + Send + Sync + 'static>(mut self, f: F) -> Self {
+        self.map_name = Some(Arc::new(f));
+        self
+    }
+    /// Record `uid` and `uname` as the owner of every entry
+    pub fn owner(mut self, uid: u32, uname: &str) -> Self {
+        self.owner = Some((uid, uname.to_string()));
+        self
+    }
+    /// Record `gid` and `gname` as the group of every entry
+    pub fn group(mut self, gid: u32, gname: &str) -> Self {
+        self.group = Some((gid, gname.to_string()));
+        self
+    }
+    /// Record only numeric ids, without user and group names
+    pub fn numeric_owner(mut self, numeric_owner: bool) -> Self {
+        self.numeric_owner = numeric_owner;
+        self
+    }
+    /// Record `mtime` (Unix seconds) as the modification time of every entry
+    pub fn mtime(mut self, mtime: u64) -> Self {
+        self.mtime = Some(mtime);
+        self
+    }
+    /// Make the output depend only on the file names and contents
+    ///
+    /// Entries are sorted by name, the modification time is 0 unless set
+    /// with `mtime`, and the owner is 0:0 without names unless set with
+    /// `owner` and `group`.
+    pub fn reproducible(mut self, reproducible: bool) -> Self {
+        self.reproducible = reproducible;
+        self
+    }
+
+    /// The codec for `tarfile`
+    pub(crate) fn compression_for(&self, tarfile: &str) -> Compression {
+        self.compression.unwrap_or_else(|| Compression::from_path(tarfile))
+    }
+
+    /// Compression settings
+    pub(crate) fn compress(&self) -> &CompressOptions {
+        &self.compress
+    }
+
+    /// True if `name` (relative to the packed directory) is excluded
+    fn is_excluded(&self, name: &str) -> bool {
+        self.exclude.iter().any(|pattern| {
+            glob_match(pattern, name)
+                || (!pattern.contains('/') && name.split('/').any(|part| glob_match(pattern, part)))
+        })
+    }
+
+    /// Entry name for a relative path, or None if nothing is left of it
+    fn entry_name(&self, relative: &str) -> Option<String> {
+        let parts: Vec<&str> = relative.split('/').filter(|p| !p.is_empty()).collect();
+        let stripped = parts.get(self.strip_components..)?.join("/");
+        let name = match &self.map_name {
+            Some(f) => f(&stripped),
+            None => stripped,
+        };
+        if name.is_empty() {
+            return None;
+        }
+        if self.prefix.is_empty() {
+            Some(name)
+        } else {
+            Some(format!("{}/{}", self.prefix, name))
+        }
+    }
+
+    /// Apply the format, ownership and time settings to a header
+    fn apply(&self, header: &mut TarHeader) {
+        if let Some(mtime) = self.mtime {
+            header.mtime = mtime;
+        } else if self.reproducible {
+            header.mtime = 0;
+        }
+        if self.reproducible {
+            header.uid = 0;
+            header.gid = 0;
+            header.uname.clear();
+            header.gname.clear();
+        }
+        if let Some((uid, uname)) = &self.owner {
+            header.uid = *uid;
+            header.uname = uname.clone();
+        }
+        if let Some((gid, gname)) = &self.group {
+            header.gid = *gid;
+            header.gname = gname.clone();
+        }
+        if self.numeric_owner {
+            header.uname.clear();
+            header.gname.clear();
+        }
+        match self.format {
+            ArchiveFormat::Ustar => {}
+            ArchiveFormat::Gnu => {
+                header.magic = "ustar ".to_string();
+                header.version = " ".to_string();
+            }
+            ArchiveFormat::V7 => {
+                header.magic.clear();
+                header.version.clear();
+                header.uname.clear();
+                header.gname.clear();
+            }
+        }
+    }
+
+    /// Metadata of `path`, of the link target only when links are followed
+    fn metadata(&self, path: &Path) -> Result<fs::Metadata, io::Error> {
+        match self.symlinks {
+            SymlinkPolicy::Follow => fs::metadata(path),
+            SymlinkPolicy::Preserve | SymlinkPolicy::Skip => fs::symlink_metadata(path),
+        }
+    }
+
+    /// True if a file with this metadata is a symbolic link to leave out
+    fn skips(&self, metadata: &fs::Metadata) -> bool {
+        self.symlinks == SymlinkPolicy::Skip && metadata.file_type().is_symlink()
+    }
+}
+
+// ----------------------------------------------------------------
+// Helper functions for recursive directory packing
+// ----------------------------------------------------------------
+/// Decides whether a file is added, given its header built from metadata
+pub(crate) type EntryFilter<'a> = &'a dyn Fn(&TarHeader) -> bool;
+
+/// Collects entries for the given files and directories accepted by `filter`
+///
+/// Without a base directory a file is named after itself and the files of
+/// a directory by their path below it, as `pack` does.
+pub(crate) fn collect_entries(
+    files: &[&str],
+    options: &PackOptions,
+    filter: EntryFilter,
+    warnings: &mut Vec<String>,
+) -> Vec<TarEntry> {
+    let mut entries = Vec::new();
+
+    for file_path in files {
+        let path = match &options.base_dir {
+            Some(dir) => dir.join(file_path),
+            None => PathBuf::from(file_path),
+        };
+        let Ok(metadata) = options.metadata(&path) else {
+            warnings.push(format!("File not found: {}", file_path));
+            continue;
+        };
+        if options.skips(&metadata) {
+            continue;
+        }
+        let base = match &options.base_dir {
+            Some(dir) => dir.clone(),
+            // Add a directory's contents, or a single file under its own name
+            None if metadata.is_dir() => path.clone(),
+            None => path.parent().unwrap_or_else(|| Path::new("")).to_path_buf(),
+        };
+        // An excluded argument is skipped with everything below it
+        let relative_path = relative_name(&path, &base);
+        if !relative_path.is_empty() && options.is_excluded(&relative_path) {
+            continue;
+        }
+
+        // Add the directory itself, unless its contents are packed without it
+        add_to_entries(&path, &base, &metadata, &mut entries, options, filter, warnings);
+        if metadata.is_dir() {
+            collect_files_from_dir(&path, &base, &mut entries, options, filter, &mut Vec::new(), warnings);
+        }
+    }
+    if options.reproducible {
+        entries.sort_by(|a, b| a.header.name.cmp(&b.header.name));
+    }
+    entries
+}
+
+/// Path of `path` below `base_path`, with '/' separators
+fn relative_name(path: &Path, base_path: &Path) -> String {
+    path.strip_prefix(base_path)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Adds a single file, directory or symbolic link to entries
+/// File data is not read here; it is streamed when the archive is written
+fn add_to_entries(
+    file_path: &Path,
+    base_path: &Path,
+    metadata: &fs::Metadata,
+    entries: &mut Vec<TarEntry>,
+    options: &PackOptions,
+    filter: EntryFilter,
+    warnings: &mut Vec<String>,
+) {
+    // Calculate relative path from base_path
+    let relative_path = relative_name(file_path, base_path);
+    if options.is_excluded(&relative_path) {
+        return;
+    }
+    let Some(name) = options.entry_name(&relative_path) else {
+        return;
+    };
+
+    let m = metadata;
+    let mut header = TarHeader::new(name, m.mode(), 0);
+    header.mtime = m.modified().unwrap_or(std::time::SystemTime::UNIX_EPOCH)
+        .duration_since(std::time::SystemTime::UNIX_EPOCH).unwrap_or_default().as_secs();
+    header.gid = m.gid();
+    header.uid = m.uid();
+    // Set uname and gname from uid/gid
+    if let Some(uname) = get_username_from_uid(m.uid()) {
+        header.uname = uname;
+    }
+    if let Some(gname) = get_groupname_from_gid(m.gid()) {
+        header.gname = gname;
+    }
+    let data = if m.is_dir() {
+        // Directory names end with '/' and carry no data
+        header.name.push('/');
+        if header.name.len() > 100 {
+            warnings.push(format!("Skipping directory {}: name longer than 100 bytes", file_path.display()));
+            return;
+        }
+        header.typeflag = TYPE_DIRECTORY;
+        EntryData::default()
+    } else if m.file_type().is_symlink() {
+        match fs::read_link(file_path) {
+            Ok(target) => header.linkname = target.to_string_lossy().to_string(),
+            Err(e) => {
+                warnings.push(format!("Error reading link {}: {}", file_path.display(), e));
+                return;
+            }
+        }
+        // The linkname field has no prefix like the name, so longer targets cannot be stored
+        if header.linkname.len() > 100 {
+            warnings.push(format!("Skipping link {}: target longer than 100 bytes", file_path.display()));
+            return;
+        }
+        header.typeflag = TYPE_SYMLINK;
+        EntryData::default()
+    } else {
+        EntryData::Path { path: file_path.to_path_buf(), len: m.len() }
+    };
+    if !filter(&header) {
+        return;
+    }
+    options.apply(&mut header);
+    header.size = data.len();
+    let header_bytes = header.to_bytes();
+
+    entries.push(TarEntry {
+        header,
+        data,
+        header_bytes,
+    });
+}
+
+/// Recursively collects all files and subdirectories of a directory
+/// `ancestors` holds the (device, inode) of the directories being walked,
+/// so a followed link back to one of them is not walked again
+fn collect_files_from_dir(
+    dir_path: &Path,
+    base_path: &Path,
+    entries: &mut Vec<TarEntry>,
+    options: &PackOptions,
+    filter: EntryFilter,
+    ancestors: &mut Vec<(u64, u64)>,
+    warnings: &mut Vec<String>,
+) {
+    let id = match fs::metadata(dir_path) {
+        Ok(m) => (m.dev(), m.ino()),
+        Err(e) => {
+            warnings.push(format!("Error reading directory {}: {}", dir_path.display(), e));
+            return;
+        }
+    };
+    if ancestors.contains(&id) {
+        warnings.push(format!("Skipping directory cycle at {}", dir_path.display()));
+        return;
+    }
+    let read_dir = match fs::read_dir(dir_path) {
+        Ok(d) => d,
+        Err(e) => {
+            warnings.push(format!("Error reading directory {}: {}", dir_path.display(), e));
+            return;
+        }
+    };
+    ancestors.push(id);
+
+    for entry_result in read_dir {
+        let entry = match entry_result {
+            Ok(e) => e,
+            Err(e) => {
+                warnings.push(format!("Error reading directory entry in {}: {}", dir_path.display(), e));
+                continue;
+            }
+        };
+
+        let path = entry.path();
+        let metadata = match options.metadata(&path) {
+            Ok(m) => m,
+            Err(e) => {
+                warnings.push(format!("Error getting metadata for {}: {}", path.display(), e));
+                continue;
+            }
+        };
+        if options.skips(&metadata) {
+            continue;
+        }
+
+        if metadata.is_dir() {
+            // An excluded directory is skipped with everything below it
+            if options.is_excluded(&relative_name(&path, base_path)) {
+                continue;
+            }
+            // Add the directory, then process its contents
+            add_to_entries(&path, base_path, &metadata, entries, options, filter, warnings);
+            collect_files_from_dir(&path, base_path, entries, options, filter, ancestors, warnings);
+        } else if metadata.is_file() || metadata.file_type().is_symlink() {
+            // Add file or link to entries
+            add_to_entries(&path, base_path, &metadata, entries, options, filter, warnings);
+        }
+    }
+    ancestors.pop();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entry_name_test() {
+        let options = PackOptions::new().strip_components(1).prefix("out/");
+        assert_eq!(options.entry_name("src/a/b.rs"), Some("out/a/b.rs".to_string()));
+        assert_eq!(options.entry_name("top.txt"), None);
+
+        let options = PackOptions::new().map_name(|name| name.replace(".txt", ".md"));
+        assert_eq!(options.entry_name("docs/a.txt"), Some("docs/a.md".to_string()));
+        let options = PackOptions::new().map_name(|_| String::new());
+        assert_eq!(options.entry_name("docs/a.txt"), None);
+    }
+
+    #[test]
+    fn exclude_test() {
+        let options = PackOptions::new().exclude("*.log").exclude("target").exclude("docs/*.md");
+        assert!(options.is_excluded("app.log"));
+        assert!(options.is_excluded("logs/app.log"));
+        assert!(options.is_excluded("target"));
+        assert!(options.is_excluded("sub/target/a.o"));
+        assert!(options.is_excluded("docs/readme.md"));
+        assert!(!options.is_excluded("sub/docs/readme.md"));
+        assert!(!options.is_excluded("src/main.rs"));
+    }
+
+    #[test]
+    fn apply_header_test() {
+        let header = || {
+            let mut h = TarHeader::new("a.txt".to_string(), 0o644, 0);
+            (h.uid, h.gid, h.mtime) = (1000, 1000, 12345);
+            (h.uname, h.gname) = ("user".to_string(), "staff".to_string());
+            h
+        };
+
+        let mut h = header();
+        PackOptions::new().reproducible(true).apply(&mut h);
+        assert_eq!((h.uid, h.gid, h.mtime), (0, 0, 0));
+        assert_eq!((h.uname.as_str(), h.gname.as_str()), ("", ""));
+
+        let mut h = header();
+        PackOptions::new().reproducible(true).mtime(99).owner(1, "daemon").apply(&mut h);
+        assert_eq!((h.uid, h.mtime, h.uname.as_str()), (1, 99, "daemon"));
+
+        let mut h = header();
+        PackOptions::new().group(50, "wheel").numeric_owner(true).apply(&mut h);
+        assert_eq!((h.uid, h.gid), (1000, 50));
+        assert_eq!((h.uname.as_str(), h.gname.as_str()), ("", ""));
+
+        let mut h = header();
+        PackOptions::new().format(ArchiveFormat::Gnu).apply(&mut h);
+        assert_eq!(&h.to_bytes()[257..265], b"ustar  \0");
+        let mut h = header();
+        PackOptions::new().format(ArchiveFormat::V7).apply(&mut h);
+        assert!(h.to_bytes()[257..329].iter().all(|&b| b == 0));
+    }
+}
@@ -0,0 +1,100 @@
+//! Multi-threaded gzip compression
+//!
+//! The input is cut into blocks that are compressed on worker threads, each
+//! as a complete gzip member. The members are written in order, giving a
+//! standard multi-member stream that gzip, pigz and `MultiGzDecoder` read
+//! as one file.
+
+use crate::compression::GzipHeader;
+use std::io::{self, Write};
+use std::thread;
+
+/// Uncompressed size of one gzip member
+pub(crate) const BLOCK_SIZE: usize = 1 << 20;
+
+/// Gzip encoder compressing blocks on `threads` worker threads
+pub(crate) struct ParallelGzEncoder<W: Write> {
+    writer: W,
+    level: flate2::Compression,
+    threads: usize,
+    header: GzipHeader,
+    /// Block being filled
+    block: Vec<u8>,
+    /// Full blocks waiting for a free worker
+    pending: Vec<Vec<u8>>,
+    members: usize,
+}
+
+impl<W: Write> ParallelGzEncoder<W> {
+    pub(crate) fn new(writer: W, level: flate2::Compression, threads: usize, header: GzipHeader) -> Self {
+        Self {
+            writer,
+            level,
+            threads: threads.max(1),
+            header,
+            block: Vec::with_capacity(BLOCK_SIZE),
+            pending: Vec::new(),
+            members: 0,
+        }
+    }
+
+    /// Compress the pending blocks in parallel and write them in order
+    fn compress_pending(&mut self) -> Result<(), io::Error> {
+        let (level, header) = (self.level, &self.header);
+        let first = self.members == 0;
+        let blocks = std::mem::take(&mut self.pending);
+        let members = thread::scope(|scope| {
+            let workers: Vec<_> = blocks
+                .iter()
+                .enumerate()
+                .map(|(i, block)| scope.spawn(move || gzip_member(block, level, header, first && i == 0)))
+                .collect();
+            workers
+                .into_iter()
+                .map(|worker| worker.join().unwrap_or_else(|_| Err(io::Error::other("gzip worker panicked"))))
+                .collect::<Result<Vec<_>, io::Error>>()
+        })?;
+        for member in members {
+            self.writer.write_all(&member)?;
+            self.members += 1;
+        }
+        Ok(())
+    }
+
+    /// Compress the remaining data and return the inner writer
+    pub(crate) fn finish(mut self) -> Result<W, io::Error> {
+        // an empty input still needs one member to be valid gzip
+        if !self.block.is_empty() || self.members + self.pending.len() == 0 {
+            self.pending.push(std::mem::take(&mut self.block));
+        }
+        self.compress_pending()?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write> Write for ParallelGzEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = buf.len().min(BLOCK_SIZE - self.block.len());
+        self.block.extend_from_slice(&buf[..n]);
+        if self.block.len() == BLOCK_SIZE {
+            let block = std::mem::replace(&mut self.block, Vec::with_capacity(BLOCK_SIZE));
+            self.pending.push(block);
+            if self.pending.len() == self.threads {
+                self.compress_pending()?;
+            }
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Compress one block as a complete gzip member
+fn gzip_member(block: &[u8], level: flate2::Compression, header: &GzipHeader, first: bool) -> Result<Vec<u8>, io::Error> {
+    let mut encoder = header.builder(first).write(Vec::with_capacity(block.len() / 2 + 64), level);
+    encoder.write_all(block)?;
+    encoder.finish()
+}
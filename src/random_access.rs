@@ -0,0 +1,119 @@
+//! Shared parts of the random-access archive formats
+//!
+//! Seekable zstd and indexed gzip archives both store the tar stream as
+//! independently compressed pieces plus a table locating them. The helpers
+//! here read byte ranges and entry headers through such a table.
+
+use crate::tar::{self, TarHeader};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::fs::FileExt;
+
+/// Location of one compressed piece (zstd frame or gzip member) in the
+/// compressed and decompressed streams
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Frame {
+    pub(crate) compressed_offset: u64,
+    pub(crate) compressed_size: u64,
+    pub(crate) offset: u64,
+    pub(crate) size: u64,
+}
+
+/// Counts the bytes written to the inner writer
+pub(crate) struct CountingWriter<W> {
+    pub(crate) inner: W,
+    pub(crate) count: u64,
+}
+
+impl<W> CountingWriter<W> {
+    pub(crate) fn new(inner: W) -> Self {
+        Self { inner, count: 0 }
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Size of the decompressed stream described by `frames`
+pub(crate) fn decompressed_len(frames: &[Frame]) -> u64 {
+    frames.last().map(|f| f.offset + f.size).unwrap_or(0)
+}
+
+/// Reader over the compressed bytes of one frame, using positioned reads
+/// so the file can be shared without seeking
+pub(crate) struct FrameReader<'a> {
+    file: &'a File,
+    pos: u64,
+    end: u64,
+}
+
+impl<'a> FrameReader<'a> {
+    pub(crate) fn new(file: &'a File, frame: &Frame) -> Self {
+        Self { file, pos: frame.compressed_offset, end: frame.compressed_offset + frame.compressed_size }
+    }
+}
+
+impl Read for FrameReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = buf.len().min((self.end - self.pos) as usize);
+        if n == 0 {
+            return Ok(0);
+        }
+        let n = self.file.read_at(&mut buf[..n], self.pos)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+/// Decompress `len` bytes at `offset`, reading only the frames covering
+/// the range; `decoder` wraps the compressed bytes of one frame
+/// Frames are decoded as streams, and decoding stops once the range is read
+pub(crate) fn read_range<'a, D>(file: &'a File, frames: &[Frame], offset: u64, len: u64, decoder: D) -> Result<Vec<u8>, io::Error>
+where
+    D: Fn(FrameReader<'a>) -> Result<Box<dyn Read + 'a>, io::Error>,
+{
+    let mut out = Vec::new();
+    let first = frames.partition_point(|f| f.offset + f.size <= offset);
+    for frame in &frames[first..] {
+        if out.len() as u64 == len {
+            break;
+        }
+        let mut decoder = decoder(FrameReader::new(file, frame))?;
+        let skip = offset.saturating_sub(frame.offset);
+        io::copy(&mut (&mut decoder).take(skip), &mut io::sink())?;
+        (&mut decoder).take(len - out.len() as u64).read_to_end(&mut out)?;
+    }
+    if (out.len() as u64) < len {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "range runs past the end of the archive"));
+    }
+    Ok(out)
+}
+
+/// Headers of all entries with their data offsets, in archive order
+pub(crate) fn scan<F>(total: u64, read_at: F) -> Result<Vec<(TarHeader, [u8; 512], u64)>, io::Error>
+where
+    F: Fn(u64, u64) -> Result<Vec<u8>, io::Error>,
+{
+    let mut result = Vec::new();
+    let mut offset = 0u64;
+    while offset + 512 <= total {
+        let block: [u8; 512] = read_at(offset, 512)?.try_into().expect("read_at returns len bytes");
+        if tar::is_empty_block(&block) {
+            break;
+        }
+        let header = tar::parse_tar_header(&block);
+        let next = offset + 512 + header.size.div_ceil(512) * 512;
+        result.push((header, block, offset + 512));
+        offset = next;
+    }
+    Ok(result)
+}
@@ -0,0 +1,25 @@
+//! Result of the archive operations in the crate root
+//!
+//! Library functions do not print; they return a `Report` and leave the
+//! output to the caller, like the command-line tool in `main.rs` does.
+
+/// Summary of a pack, append, update, delete or unpack run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    /// Entries written, extracted or deleted, in archive order
+    pub entries: Vec<String>,
+    /// Extracted entries that replaced an existing file
+    pub overwritten: Vec<String>,
+    /// Entries not extracted because the file already exists
+    pub skipped: Vec<String>,
+    /// Problems that did not stop the operation, such as missing input
+    /// files or entries that could not be written
+    pub warnings: Vec<String>,
+}
+
+impl Report {
+    /// Number of entries written, extracted or deleted
+    pub fn count(&self) -> usize {
+        self.entries.len()
+    }
+}
@@ -0,0 +1,375 @@
+//! Append-only key-value store backed by a tar file
+//!
+//! Every `set` appends a new version of the key to the end of the archive
+//! by overwriting the end-of-archive marker, so existing data is never
+//! rewritten. Reads resolve to the latest version of a key. Deletes append
+//! a tombstone, an empty file named `.wh.<name>` (the whiteout convention
+//! used by OCI image layers). `compact()` rewrites the file without
+//! superseded versions and tombstones.
+//!
+//! # Crash safety
+//!
+//! An append is made durable in two steps:
+//!
+//! 1. The entry data and a new end-of-archive marker are written *after*
+//!    the first zero block of the current end marker, then fsync'ed. The
+//!    old end marker still terminates the archive, so a crash here leaves
+//!    the previous state intact.
+//! 2. The 512-byte header is written over the old end marker and fsync'ed,
+//!    which makes the new entry visible.
+//!
+//! When opening, a header with a bad checksum or data running past the end
+//! of the file is treated as the end of the archive and is overwritten by
+//! the next append. `compact()` writes a temporary file, fsyncs it and
+//! renames it over the original.
+//!
+//! The store assumes a single writer process.
+//!
+//! # Usage
+//!
+//! ```rust
+//! use tar_light::TarStore;
+//!
+//! let mut store = TarStore::open("store_doc.tar").unwrap();
+//! store.set_str("user/name", "alice").unwrap();
+//! store.set_str("user/name", "bob").unwrap();
+//! assert_eq!(store.get_str("user/name").unwrap().as_deref(), Some("bob"));
+//!
+//! store.delete("user/name").unwrap();
+//! assert!(store.get("user/name").unwrap().is_none());
+//!
+//! // Drop superseded versions and tombstones
+//! store.compact().unwrap();
+//! # std::fs::remove_file("store_doc.tar").unwrap();
+//! ```
+
+use crate::fsutil::{sync_parent_dir, temp_path_for};
+use crate::tar::{calc_checksum, now_secs, TarHeader};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+
+/// Name prefix of a tombstone entry
+const WHITEOUT_PREFIX: &str = ".wh.";
+
+/// Location of the latest version of a key
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    header_offset: u64,
+    size: u64,
+}
+
+/// Append-only key-value store backed by a tar file
+#[derive(Debug)]
+pub struct TarStore {
+    path: PathBuf,
+    file: File,
+    index: HashMap<String, Slot>,
+    end: u64,
+}
+
+impl TarStore {
+    /// Open a store, creating an empty archive if the file does not exist
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, io::Error> {
+        let path = path.as_ref().to_path_buf();
+        let exists = path.exists();
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&path)?;
+        if !exists {
+            file.write_all_at(&[0u8; 1024], 0)?;
+            file.sync_all()?;
+            sync_parent_dir(&path)?;
+        }
+        let mut store = Self {
+            path,
+            file,
+            index: HashMap::new(),
+            end: 0,
+        };
+        store.scan()?;
+        Ok(store)
+    }
+
+    /// Path of the backing tar file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Read all headers and rebuild the index
+    fn scan(&mut self) -> Result<(), io::Error> {
+        self.index.clear();
+        let file_len = self.file.metadata()?.len();
+        let mut reader = BufReader::new(&self.file);
+        reader.seek(SeekFrom::Start(0))?;
+        let mut offset = 0u64;
+        let mut block = [0u8; 512];
+        while offset + 512 <= file_len {
+            reader.read_exact(&mut block)?;
+            if block.iter().all(|&b| b == 0) {
+                break;
+            }
+            let header = TarHeader::from_bytes(&block);
+            if calc_checksum(&block) != header.checksum {
+                break; // torn write at the tail
+            }
+            let padded = header.size.div_ceil(512) * 512;
+            if offset + 512 + padded > file_len {
+                break; // incomplete data at the tail
+            }
+            if header.is_file() {
+                match whiteout_target(&header.name) {
+                    Some(name) => {
+                        self.index.remove(&name);
+                    }
+                    None => {
+                        self.index.insert(header.name.clone(), Slot { header_offset: offset, size: header.size });
+                    }
+                }
+            }
+            reader.seek_relative(padded as i64)?;
+            offset += 512 + padded;
+        }
+        self.end = offset;
+        Ok(())
+    }
+
+    /// Get the latest value of a key
+    pub fn get(&self, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        let Some(slot) = self.index.get(key) else {
+            return Ok(None);
+        };
+        let mut data = vec![0u8; slot.size as usize];
+        self.file.read_exact_at(&mut data, slot.header_offset + 512)?;
+        Ok(Some(data))
+    }
+
+    /// Get the latest value of a key as a string
+    pub fn get_str(&self, key: &str) -> Result<Option<String>, io::Error> {
+        Ok(self.get(key)?.map(|d| String::from_utf8_lossy(&d).to_string()))
+    }
+
+    /// Append a new version of a key
+    pub fn set(&mut self, key: &str, value: &[u8]) -> Result<(), io::Error> {
+        if whiteout_target(key).is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("key must not start with {}: {}", WHITEOUT_PREFIX, key),
+            ));
+        }
+        let offset = self.append(key, value)?;
+        self.index.insert(key.to_string(), Slot { header_offset: offset, size: value.len() as u64 });
+        Ok(())
+    }
+
+    /// Append a new version of a key from a string
+    pub fn set_str(&mut self, key: &str, value: &str) -> Result<(), io::Error> {
+        self.set(key, value.as_bytes())
+    }
+
+    /// Delete a key by appending a tombstone
+    /// Returns false if the key did not exist
+    pub fn delete(&mut self, key: &str) -> Result<bool, io::Error> {
+        if !self.index.contains_key(key) {
+            return Ok(false);
+        }
+        self.append(&whiteout_name(key), &[])?;
+        self.index.remove(key);
+        Ok(true)
+    }
+
+    /// Returns true if the key exists
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.index.contains_key(key)
+    }
+
+    /// List the live keys in archive order
+    pub fn keys(&self) -> Vec<&str> {
+        let mut keys: Vec<(&String, &Slot)> = self.index.iter().collect();
+        keys.sort_by_key(|(_, slot)| slot.header_offset);
+        keys.into_iter().map(|(k, _)| k.as_str()).collect()
+    }
+
+    /// Number of live keys
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    /// Returns true if the store has no live keys
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Write one entry at the end of the archive and return its header offset
+    fn append(&mut self, name: &str, data: &[u8]) -> Result<u64, io::Error> {
+        let header = TarHeader::builder()
+            .name(name)
+            .mode(0o664)
+            .size(data.len() as u64)
+            .mtime(now_secs())
+            .build()?;
+        let header_bytes = header.to_bytes();
+        let offset = self.end;
+
+        // 1. data, padding and the new end marker behind the current end marker
+        let padding = (512 - data.len() % 512) % 512;
+        let mut body = Vec::with_capacity(data.len() + padding + 1024);
+        body.extend_from_slice(data);
+        body.resize(data.len() + padding + 1024, 0);
+        self.file.write_all_at(&body, offset + 512)?;
+        self.file.sync_data()?;
+
+        // 2. the header over the old end marker makes the entry visible
+        self.file.write_all_at(&header_bytes, offset)?;
+        self.file.sync_data()?;
+
+        self.end = offset + 512 + (data.len() + padding) as u64;
+        Ok(offset)
+    }
+
+    /// Rewrite the archive without superseded versions and tombstones
+    pub fn compact(&mut self) -> Result<(), io::Error> {
+        let mut slots: Vec<Slot> = self.index.values().copied().collect();
+        slots.sort_by_key(|s| s.header_offset);
+
+        let tmp_path = temp_path_for(&self.path);
+        let result = (|| {
+            let mut tmp = File::create(&tmp_path)?;
+            for slot in &slots {
+                let padded = slot.size.div_ceil(512) * 512;
+                let mut block = vec![0u8; (512 + padded) as usize];
+                self.file.read_exact_at(&mut block[..512 + slot.size as usize], slot.header_offset)?;
+                tmp.write_all(&block)?;
+            }
+            tmp.write_all(&[0u8; 1024])?;
+            tmp.sync_all()
+        })();
+        if let Err(e) = result {
+            fs::remove_file(&tmp_path).ok();
+            return Err(e);
+        }
+        fs::rename(&tmp_path, &self.path)?;
+        sync_parent_dir(&self.path)?;
+
+        self.file = OpenOptions::new().read(true).write(true).open(&self.path)?;
+        self.scan()
+    }
+}
+
+/// Tombstone name for a key: `dir/name` becomes `dir/.wh.name`
+fn whiteout_name(key: &str) -> String {
+    match key.rsplit_once('/') {
+        Some((dir, base)) => format!("{}/{}{}", dir, WHITEOUT_PREFIX, base),
+        None => format!("{}{}", WHITEOUT_PREFIX, key),
+    }
+}
+
+/// Key deleted by a tombstone entry, or None if the name is not a tombstone
+fn whiteout_target(name: &str) -> Option<String> {
+    let (dir, base) = match name.rsplit_once('/') {
+        Some((dir, base)) => (Some(dir), base),
+        None => (None, name),
+    };
+    let base = base.strip_prefix(WHITEOUT_PREFIX)?;
+    Some(match dir {
+        Some(dir) => format!("{}/{}", dir, base),
+        None => base.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tar::read_tar;
+
+    #[test]
+    fn store_set_get_test() {
+        let path = "test_store_set_get.tar";
+        fs::remove_file(path).ok();
+        {
+            let mut store = TarStore::open(path).unwrap();
+            assert!(store.is_empty());
+            store.set_str("a.txt", "first").unwrap();
+            store.set("b.bin", &[0, 1, 2, 0]).unwrap();
+            store.set_str("a.txt", "second").unwrap();
+            assert_eq!(store.get_str("a.txt").unwrap().as_deref(), Some("second"));
+            assert_eq!(store.get("b.bin").unwrap(), Some(vec![0, 1, 2, 0]));
+            assert_eq!(store.keys(), vec!["b.bin", "a.txt"]);
+        }
+        // Reopen and check that the latest version wins
+        let store = TarStore::open(path).unwrap();
+        assert_eq!(store.get_str("a.txt").unwrap().as_deref(), Some("second"));
+        assert_eq!(store.len(), 2);
+
+        // The file is a valid tar archive containing every version
+        let entries = read_tar(&fs::read(path).unwrap());
+        assert_eq!(entries.len(), 3);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn store_delete_and_compact_test() {
+        let path = "test_store_compact.tar";
+        fs::remove_file(path).ok();
+        let mut store = TarStore::open(path).unwrap();
+        store.set_str("dir/keep.txt", "keep").unwrap();
+        store.set_str("dir/gone.txt", "gone").unwrap();
+        store.set_str("dir/keep.txt", "keep v2").unwrap();
+        assert!(store.delete("dir/gone.txt").unwrap());
+        assert!(!store.delete("dir/gone.txt").unwrap());
+        assert!(!store.contains_key("dir/gone.txt"));
+        assert!(store.set_str("dir/.wh.x", "no").is_err());
+
+        // The tombstone survives a reopen
+        let mut store = TarStore::open(path).unwrap();
+        assert!(store.get("dir/gone.txt").unwrap().is_none());
+        let before = fs::metadata(path).unwrap().len();
+
+        store.compact().unwrap();
+        assert!(fs::metadata(path).unwrap().len() < before);
+        let entries = read_tar(&fs::read(path).unwrap());
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].header.name, "dir/keep.txt");
+        assert_eq!(entries[0].data, b"keep v2");
+
+        // Appending after compaction still works
+        store.set_str("new.txt", "new").unwrap();
+        let store = TarStore::open(path).unwrap();
+        assert_eq!(store.keys(), vec!["dir/keep.txt", "new.txt"]);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn store_torn_write_test() {
+        let path = "test_store_torn.tar";
+        fs::remove_file(path).ok();
+        let mut store = TarStore::open(path).unwrap();
+        store.set_str("ok.txt", "ok").unwrap();
+        let end = store.end;
+        drop(store);
+
+        // Simulate a crash while writing a header: garbage over the end marker
+        let file = OpenOptions::new().write(true).open(path).unwrap();
+        file.write_all_at(&[0x41u8; 512], end).unwrap();
+        drop(file);
+
+        let mut store = TarStore::open(path).unwrap();
+        assert_eq!(store.keys(), vec!["ok.txt"]);
+        assert_eq!(store.end, end);
+        store.set_str("next.txt", "next").unwrap();
+        let store = TarStore::open(path).unwrap();
+        assert_eq!(store.get_str("next.txt").unwrap().as_deref(), Some("next"));
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn whiteout_name_test() {
+        assert_eq!(whiteout_name("a.txt"), ".wh.a.txt");
+        assert_eq!(whiteout_name("dir/a.txt"), "dir/.wh.a.txt");
+        assert_eq!(whiteout_target("dir/.wh.a.txt").as_deref(), Some("dir/a.txt"));
+        assert_eq!(whiteout_target("dir/a.txt"), None);
+    }
+}
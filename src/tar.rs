@@ -15,7 +15,7 @@
 //! for entry in entries {
 //!     println!("File name: {}", entry.header.name);
 //!     println!("Size: {} bytes", entry.header.size);
-//!     println!("Content: {}", String::from_utf8_lossy(&entry.data));
+//!     println!("Content: {}", String::from_utf8_lossy(entry.data.as_bytes().unwrap()));
 //! }
 //! ```
 //!
@@ -33,12 +33,12 @@
 //!
 //! entries.push(TarEntry {
 //!     header,
-//!     data,
+//!     data: data.into(),
 //!     header_bytes,
 //! });
 //!
 //! // Write to TAR archive
-//! let tar_data = write_tar(&entries);
+//! let tar_data = write_tar(&entries).unwrap();
 //! std::fs::write("archive.tar", tar_data).unwrap();
 //! ```
 //!
@@ -60,9 +60,59 @@
 //! // Verify checksum
 //! let is_valid = header.verify_checksum(&bytes);
 //! ```
- 
+//!
+//! ## Building Headers and Entries
+//!
+//! ```rust
+//! use tar_light::tar::{TarHeader, TarEntry, TYPE_DIRECTORY};
+//!
+//! // Validating builder
+//! let header = TarHeader::builder()
+//!     .name("docs/")
+//!     .mode(0o755)
+//!     .typeflag(TYPE_DIRECTORY)
+//!     .build()
+//!     .unwrap();
+//! assert!(header.is_dir());
+//!
+//! // Typed constructors keep size, typeflag and header bytes in sync
+//! let file = TarEntry::file("docs/readme.txt", b"Hello".to_vec());
+//! let link = TarEntry::symlink("docs/latest.txt", "readme.txt");
+//! assert_eq!(file.header.size, 5);
+//! assert!(link.header.verify_checksum(&link.header_bytes));
+//! ```
+
+use crate::compression::{self, CompressOptions, Compression};
+use crate::fsutil::write_atomic_with;
+use crate::tree::TarTree;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
+pub use crate::entry_data::EntryData;
+
+/// Typeflag of a regular file
+pub const TYPE_REGULAR: u8 = b'0';
+/// Typeflag of a regular file written by old (pre-POSIX) tar
+pub const TYPE_REGULAR_OLD: u8 = 0;
+/// Typeflag of a hard link
+pub const TYPE_HARDLINK: u8 = b'1';
+/// Typeflag of a symbolic link
+pub const TYPE_SYMLINK: u8 = b'2';
+/// Typeflag of a character device
+pub const TYPE_CHAR_DEVICE: u8 = b'3';
+/// Typeflag of a block device
+pub const TYPE_BLOCK_DEVICE: u8 = b'4';
+/// Typeflag of a directory
+pub const TYPE_DIRECTORY: u8 = b'5';
+/// Typeflag of a FIFO (named pipe)
+pub const TYPE_FIFO: u8 = b'6';
+
 // Tar header struct
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TarHeader {
     pub name: String,
     pub mode: u32,
@@ -106,6 +156,7 @@ impl TarHeader {
         }
     }
     /// new TarHeader with additional fields
+    #[allow(clippy::too_many_arguments)]
     pub fn new_full(
         name: String,
         mode: u32,
@@ -125,6 +176,11 @@ impl TarHeader {
         header
     }
 
+    /// Create a builder that validates the fields before creating a TarHeader
+    pub fn builder() -> TarHeaderBuilder {
+        TarHeaderBuilder::new()
+    }
+
     /// Parse a TarHeader from a 512-byte slice
     pub fn from_bytes(data: &[u8]) -> Self {
         parse_tar_header(data)
@@ -141,78 +197,620 @@ impl TarHeader {
         let sum = calc_checksum(data);
         sum == self.checksum
     }
+
+    /// Returns true if the header describes a regular file
+    pub fn is_file(&self) -> bool {
+        self.typeflag == TYPE_REGULAR || self.typeflag == TYPE_REGULAR_OLD
+    }
+
+    /// Returns true if the header describes a directory
+    pub fn is_dir(&self) -> bool {
+        self.typeflag == TYPE_DIRECTORY
+    }
+
+    /// Returns true if the header describes a symbolic link
+    pub fn is_symlink(&self) -> bool {
+        self.typeflag == TYPE_SYMLINK
+    }
+
+    /// Returns true if the header describes a hard link
+    pub fn is_hardlink(&self) -> bool {
+        self.typeflag == TYPE_HARDLINK
+    }
+
+    /// Kind of entry described by the typeflag
+    pub fn kind(&self) -> EntryKind {
+        EntryKind::from_typeflag(self.typeflag)
+    }
+}
+
+/// Kind of a tar entry, decoded from the typeflag
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    File,
+    HardLink,
+    Symlink,
+    CharDevice,
+    BlockDevice,
+    Directory,
+    Fifo,
+    Other(u8),
+}
+
+impl EntryKind {
+    /// Decode a typeflag
+    pub fn from_typeflag(typeflag: u8) -> Self {
+        match typeflag {
+            TYPE_REGULAR | TYPE_REGULAR_OLD => EntryKind::File,
+            TYPE_HARDLINK => EntryKind::HardLink,
+            TYPE_SYMLINK => EntryKind::Symlink,
+            TYPE_CHAR_DEVICE => EntryKind::CharDevice,
+            TYPE_BLOCK_DEVICE => EntryKind::BlockDevice,
+            TYPE_DIRECTORY => EntryKind::Directory,
+            TYPE_FIFO => EntryKind::Fifo,
+            t => EntryKind::Other(t),
+        }
+    }
+}
+
+/// Builder for TarHeader
+///
+/// Unlike `TarHeader::new`, which silently truncates oversized fields,
+/// `build()` checks that every field fits in the ustar header and that
+/// the size, typeflag and linkname agree with each other.
+#[derive(Debug, Clone)]
+pub struct TarHeaderBuilder {
+    header: TarHeader,
+}
+
+impl Default for TarHeaderBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TarHeaderBuilder {
+    /// Create a builder for a regular file with mode 0o644
+    pub fn new() -> Self {
+        Self {
+            header: TarHeader::new(String::new(), 0o644, 0),
+        }
+    }
+    /// Set the entry name
+    pub fn name(mut self, name: &str) -> Self {
+        self.header.name = name.to_string();
+        self
+    }
+    /// Set the permission bits
+    pub fn mode(mut self, mode: u32) -> Self {
+        self.header.mode = mode;
+        self
+    }
+    /// Set the owner's user id
+    pub fn uid(mut self, uid: u32) -> Self {
+        self.header.uid = uid;
+        self
+    }
+    /// Set the owner's group id
+    pub fn gid(mut self, gid: u32) -> Self {
+        self.header.gid = gid;
+        self
+    }
+    /// Set the data size in bytes
+    pub fn size(mut self, size: u64) -> Self {
+        self.header.size = size;
+        self
+    }
+    /// Set the modification time (seconds since the Unix epoch)
+    pub fn mtime(mut self, mtime: u64) -> Self {
+        self.header.mtime = mtime;
+        self
+    }
+    /// Set the typeflag (see the `TYPE_*` constants)
+    pub fn typeflag(mut self, typeflag: u8) -> Self {
+        self.header.typeflag = typeflag;
+        self
+    }
+    /// Set the link target of a hard link or symbolic link
+    pub fn linkname(mut self, linkname: &str) -> Self {
+        self.header.linkname = linkname.to_string();
+        self
+    }
+    /// Set the owner's user name
+    pub fn uname(mut self, uname: &str) -> Self {
+        self.header.uname = uname.to_string();
+        self
+    }
+    /// Set the owner's group name
+    pub fn gname(mut self, gname: &str) -> Self {
+        self.header.gname = gname.to_string();
+        self
+    }
+    /// Set the major and minor numbers of a device entry
+    pub fn device(mut self, major: u32, minor: u32) -> Self {
+        self.header.devmajor = major;
+        self.header.devminor = minor;
+        self
+    }
+    /// Set the ustar name prefix
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.header.prefix = prefix.to_string();
+        self
+    }
+
+    /// Validate the fields and create the TarHeader
+    /// The checksum is that of the header's serialized bytes
+    pub fn build(self) -> Result<TarHeader, io::Error> {
+        let mut h = self.header;
+        if h.name.is_empty() {
+            return Err(invalid_input("name must not be empty".to_string()));
+        }
+        check_str_field("name", &h.name, 100)?;
+        check_str_field("linkname", &h.linkname, 100)?;
+        check_str_field("uname", &h.uname, 32)?;
+        check_str_field("gname", &h.gname, 32)?;
+        check_str_field("prefix", &h.prefix, 155)?;
+        check_num_field("mode", h.mode as u64, 7)?;
+        check_num_field("uid", h.uid as u64, 7)?;
+        check_num_field("gid", h.gid as u64, 7)?;
+        check_num_field("size", h.size, 11)?;
+        check_num_field("mtime", h.mtime, 11)?;
+        check_num_field("devmajor", h.devmajor as u64, 7)?;
+        check_num_field("devminor", h.devminor as u64, 7)?;
+        match h.typeflag {
+            TYPE_REGULAR | TYPE_REGULAR_OLD => {}
+            TYPE_HARDLINK | TYPE_SYMLINK => {
+                if h.linkname.is_empty() {
+                    return Err(invalid_input(format!("{}: link entry requires a linkname", h.name)));
+                }
+            }
+            TYPE_CHAR_DEVICE | TYPE_BLOCK_DEVICE | TYPE_DIRECTORY | TYPE_FIFO => {}
+            t => {
+                return Err(invalid_input(format!("{}: unsupported typeflag {:?}", h.name, t as char)));
+            }
+        }
+        if !h.is_file() && h.size != 0 {
+            return Err(invalid_input(format!("{}: only regular files can have data", h.name)));
+        }
+        if h.is_dir() && !h.name.ends_with('/') {
+            return Err(invalid_input(format!("{}: directory name must end with '/'", h.name)));
+        }
+        h.checksum = calc_checksum(&h.to_bytes());
+        Ok(h)
+    }
+}
+
+fn invalid_input(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+/// Check that a string field fits in `max` bytes and contains no NUL
+fn check_str_field(field: &str, value: &str, max: usize) -> Result<(), io::Error> {
+    if value.len() > max {
+        return Err(invalid_input(format!("{} exceeds {} bytes: {}", field, max, value)));
+    }
+    if value.contains('\0') {
+        return Err(invalid_input(format!("{} contains a NUL byte", field)));
+    }
+    Ok(())
+}
+
+/// Check that a number fits in an octal field of `digits` digits
+fn check_num_field(field: &str, value: u64, digits: u32) -> Result<(), io::Error> {
+    if value >= 8u64.pow(digits) {
+        return Err(invalid_input(format!("{} is too large for the header: {}", field, value)));
+    }
+    Ok(())
 }
 
 /// Tar entry struct
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TarEntry {
     pub header: TarHeader,
-    pub data: Vec<u8>,
+    pub data: EntryData,
     pub header_bytes: [u8; 512],
 }
 
+impl TarEntry {
+    /// Create an entry from a header and data
+    /// The header size and header_bytes are updated to match the data
+    pub fn new(header: TarHeader, data: impl Into<EntryData>) -> Self {
+        let mut entry = Self {
+            header,
+            data: data.into(),
+            header_bytes: [0u8; 512],
+        };
+        entry.refresh_header();
+        entry
+    }
+    /// Create a regular file entry (mode 0o644)
+    pub fn file(name: &str, data: impl Into<EntryData>) -> Self {
+        Self::new(TarHeader::new(name.to_string(), 0o644, 0), data)
+    }
+    /// Create a directory entry (mode 0o755)
+    /// A trailing '/' is appended to the name if missing
+    pub fn directory(name: &str) -> Self {
+        let name = if name.ends_with('/') { name.to_string() } else { format!("{}/", name) };
+        Self::special(name, 0o755, TYPE_DIRECTORY, "")
+    }
+    /// Create a symbolic link entry pointing to `target`
+    pub fn symlink(name: &str, target: &str) -> Self {
+        Self::special(name.to_string(), 0o777, TYPE_SYMLINK, target)
+    }
+    /// Create a hard link entry pointing to the archived file `target`
+    pub fn hardlink(name: &str, target: &str) -> Self {
+        Self::special(name.to_string(), 0o644, TYPE_HARDLINK, target)
+    }
+    /// Create a FIFO (named pipe) entry
+    pub fn fifo(name: &str) -> Self {
+        Self::special(name.to_string(), 0o644, TYPE_FIFO, "")
+    }
+    /// Create a character device entry
+    pub fn char_device(name: &str, major: u32, minor: u32) -> Self {
+        let mut entry = Self::special(name.to_string(), 0o644, TYPE_CHAR_DEVICE, "");
+        entry.header.devmajor = major;
+        entry.header.devminor = minor;
+        entry.refresh_header();
+        entry
+    }
+    /// Create a block device entry
+    pub fn block_device(name: &str, major: u32, minor: u32) -> Self {
+        let mut entry = Self::special(name.to_string(), 0o644, TYPE_BLOCK_DEVICE, "");
+        entry.header.devmajor = major;
+        entry.header.devminor = minor;
+        entry.refresh_header();
+        entry
+    }
+
+    fn special(name: String, mode: u32, typeflag: u8, linkname: &str) -> Self {
+        let mut header = TarHeader::new(name, mode, 0);
+        header.typeflag = typeflag;
+        header.linkname = linkname.to_string();
+        Self::new(header, EntryData::default())
+    }
+
+    /// Set the permission bits and update header_bytes
+    pub fn with_mode(mut self, mode: u32) -> Self {
+        self.header.mode = mode;
+        self.refresh_header();
+        self
+    }
+    /// Set the modification time and update header_bytes
+    pub fn with_mtime(mut self, mtime: u64) -> Self {
+        self.header.mtime = mtime;
+        self.refresh_header();
+        self
+    }
+
+    /// Recalculate header size, checksum and header_bytes after modifying the entry
+    /// Non-regular entries never carry data, so their data is dropped and their size is 0
+    pub fn refresh_header(&mut self) {
+        if !self.header.is_file() {
+            self.data = EntryData::default();
+        }
+        self.header.size = self.data.len();
+        self.header_bytes = self.header.to_bytes();
+        self.header.checksum = calc_checksum(&self.header_bytes);
+    }
+}
+
+/// Mutable access to an entry of a Tar archive
+/// Calls `TarEntry::refresh_header` when dropped, so size and checksum
+/// always match the modified data, and reindexes the archive if the
+/// entry was renamed
+#[derive(Debug)]
+pub struct TarEntryMut<'a> {
+    tar: &'a mut Tar,
+    index: usize,
+    old_name: String,
+}
+
+impl std::ops::Deref for TarEntryMut<'_> {
+    type Target = TarEntry;
+    fn deref(&self) -> &TarEntry {
+        &self.tar.entries[self.index]
+    }
+}
+
+impl std::ops::DerefMut for TarEntryMut<'_> {
+    fn deref_mut(&mut self) -> &mut TarEntry {
+        &mut self.tar.entries[self.index]
+    }
+}
+
+impl Drop for TarEntryMut<'_> {
+    fn drop(&mut self) {
+        let entry = &mut self.tar.entries[self.index];
+        entry.refresh_header();
+        if entry.header.name != self.old_name {
+            self.tar.reindex();
+        }
+    }
+}
+
+/// Match a name against a glob pattern (see `Tar::entries_matching`)
+pub(crate) fn glob_match(pattern: &str, name: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let n: Vec<char> = name.chars().collect();
+    glob_match_at(&p, &n)
+}
+
+fn glob_match_at(p: &[char], n: &[char]) -> bool {
+    match p.first() {
+        None => n.is_empty(),
+        Some('*') if p.get(1) == Some(&'*') => {
+            // `**/` also matches zero directories
+            let rest = &p[2..];
+            if rest.first() == Some(&'/') && glob_match_at(&rest[1..], n) {
+                return true;
+            }
+            (0..=n.len()).any(|i| glob_match_at(rest, &n[i..]))
+        }
+        Some('*') => {
+            let rest = &p[1..];
+            for i in 0..=n.len() {
+                if glob_match_at(rest, &n[i..]) {
+                    return true;
+                }
+                if i < n.len() && n[i] == '/' {
+                    break;
+                }
+            }
+            false
+        }
+        Some('?') => !n.is_empty() && n[0] != '/' && glob_match_at(&p[1..], &n[1..]),
+        Some('[') => {
+            let Some(close) = p.iter().skip(2).position(|&c| c == ']').map(|i| i + 2) else {
+                return n.first() == Some(&'[') && glob_match_at(&p[1..], &n[1..]);
+            };
+            let Some(&c) = n.first() else { return false };
+            let (negate, set) = match p[1] {
+                '!' | '^' => (true, &p[2..close]),
+                _ => (false, &p[1..close]),
+            };
+            let mut hit = false;
+            let mut i = 0;
+            while i < set.len() {
+                if i + 2 < set.len() && set[i + 1] == '-' {
+                    hit |= set[i] <= c && c <= set[i + 2];
+                    i += 3;
+                } else {
+                    hit |= set[i] == c;
+                    i += 1;
+                }
+            }
+            hit != negate && c != '/' && glob_match_at(&p[close + 1..], &n[1..])
+        }
+        Some(&c) => n.first() == Some(&c) && glob_match_at(&p[1..], &n[1..]),
+    }
+}
+
 // Tar struct
+//
+// Lookups by name go through an index of entry positions. As in GNU tar,
+// when an archive contains the same name several times, the last
+// occurrence is the authoritative one.
 #[derive(Debug)]
 pub struct Tar {
+    /// The entries in archive order
+    ///
+    /// The name index is kept up to date by the methods of `Tar`, and is
+    /// rebuilt by the next lookup whenever the number of entries changed.
+    /// After renaming, reordering or replacing entries in place, call `reindex` before
+    /// looking entries up by name or browsing the tree; until then lookups
+    /// may miss entries, but never return an entry with another name.
     pub entries: Vec<TarEntry>,
-    pub use_header_parsing: bool, // if true, update TarEntry.header_bytes on modification
+    pub use_header_parsing: bool, // kept for compatibility; header_bytes are always kept up to date
+    index: HashMap<String, Vec<usize>>,
+    indexed_len: usize,
+    /// Directory tree view, built on first use and dropped on changes
+    pub(crate) tree: OnceLock<TarTree>,
+}
+impl Default for Tar {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 impl Tar {
     /// Create a new empty Tar archive
     pub fn new() -> Self {
-        Self {
-            entries: Vec::new(),
-            use_header_parsing: false,
-        }
+        Self::from_entries(Vec::new())
     }
     /// Create a Tar archive from bytes
     pub fn from_bytes(data: &[u8]) -> Self {
-        let entries = read_tar(data);
-        Self {
+        Self::from_entries(read_tar(data))
+    }
+    /// Open a tar archive file
+    /// Compression is detected from the file content
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, io::Error> {
+        Self::open_with(path, &CompressOptions::default())
+    }
+    /// Open a tar archive file compressed with a zstd dictionary
+    /// Only `options.zstd.dictionary` is used
+    pub fn open_with<P: AsRef<Path>>(path: P, options: &CompressOptions) -> Result<Self, io::Error> {
+        let data = compression::decompress_detected(std::fs::read(path)?, options)?;
+        Ok(Self::from_bytes(&data))
+    }
+    /// Open an uncompressed tar archive without loading entry data
+    ///
+    /// Only the headers are read; entry data refers to byte ranges of the
+    /// open file and is read on demand. The file must not be modified in
+    /// place (by `append` or `delete`) while the archive is in use, but it
+    /// may be replaced by `save`.
+    pub fn open_lazy<P: AsRef<Path>>(path: P) -> Result<Self, io::Error> {
+        Ok(Self::from_entries(read_tar_file(path)?))
+    }
+    /// Save the archive to a file
+    ///
+    /// Compression is chosen from the file name by `Compression::from_path`:
+    /// `.gz` / `.tgz` for gzip, `.bz2` / `.tbz2` / `.tbz` for bzip2,
+    /// `.xz` / `.txz` for xz and `.zst` / `.tzst` for zstd; other names are
+    /// written as plain tar. bzip2, xz and zstd need their cargo feature;
+    /// `.Z` / `.taz` (compress) can only be read and `.lz4` is not supported,
+    /// so saving to those names fails with `ErrorKind::Unsupported`.
+    /// The file is replaced atomically: the archive is streamed to a temporary
+    /// file which is renamed over `path` once it is complete.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), io::Error> {
+        self.save_with(path, &CompressOptions::default())
+    }
+    /// Save the archive to a file with compression settings
+    /// (level, zstd long-distance matching, dictionary, seekable format)
+    pub fn save_with<P: AsRef<Path>>(&self, path: P, options: &CompressOptions) -> Result<(), io::Error> {
+        let path = path.as_ref();
+        let compression = Compression::from_path(path);
+        write_atomic_with(path, |file| {
+            let mut encoder = compression::Encoder::new(file, compression, options)?;
+            write_tar_encoded(&self.entries, &mut encoder)?;
+            encoder.finish()?;
+            Ok(())
+        })
+    }
+    /// Stream the archive to a writer
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), io::Error> {
+        write_tar_to(&self.entries, writer)
+    }
+    /// Create a Tar archive from a list of entries
+    pub fn from_entries(entries: Vec<TarEntry>) -> Self {
+        let mut tar = Self {
             entries,
             use_header_parsing: false,
+            index: HashMap::new(),
+            indexed_len: 0,
+            tree: OnceLock::new(),
+        };
+        tar.reindex();
+        tar
+    }
+    /// Rebuild the name index and drop the tree view
+    /// Call this after modifying `entries` directly
+    pub fn reindex(&mut self) {
+        self.tree.take();
+        self.index.clear();
+        for (i, entry) in self.entries.iter().enumerate() {
+            self.index.entry(entry.header.name.clone()).or_default().push(i);
+        }
+        self.indexed_len = self.entries.len();
+    }
+    /// Positions of all entries with the given name, in archive order
+    /// If `entries` grew or shrank since the last reindex, they are scanned instead
+    fn positions(&self, name: &str) -> Cow<'_, [usize]> {
+        if self.indexed_len == self.entries.len() {
+            return Cow::Borrowed(self.index.get(name).map_or(&[], Vec::as_slice));
         }
+        Cow::Owned(
+            self.entries
+                .iter()
+                .enumerate()
+                .filter(|(_, e)| e.header.name == name)
+                .map(|(i, _)| i)
+                .collect(),
+        )
+    }
+    /// Rebuild the index if entries were added or removed directly
+    fn sync_index(&mut self) {
+        if self.indexed_len != self.entries.len() {
+            self.reindex();
+        }
+    }
+    /// The entry at `i`, if it still has the given name
+    /// Guards lookups against an index made stale by direct changes to `entries`
+    fn entry_at(&self, i: usize, name: &str) -> Option<&TarEntry> {
+        self.entries.get(i).filter(|e| e.header.name == name)
     }
     /// Add an entry to the Tar archive
     pub fn add_entry(&mut self, entry: TarEntry) {
+        self.sync_index();
+        self.tree.take();
+        self.index.entry(entry.header.name.clone()).or_default().push(self.entries.len());
         self.entries.push(entry);
+        self.indexed_len = self.entries.len();
     }
     /// Add string data to the Tar archive
     pub fn add_str_entry(&mut self, name: &str, content: &str) {
-        let data = content.as_bytes().to_vec();
-        let mut header = TarHeader::new(name.to_string(), 0o664, data.len() as u64);
-        header.typeflag = b'0'; // 通常ファイルとして明示
-        let mut header_bytes = [0u8; 512];
-        if self.use_header_parsing {
-            header_bytes = header.to_bytes();
-        }
-        let entry = TarEntry {
-            header,
-            data,
-            header_bytes,
-        };
-        self.entries.push(entry);
+        let entry = TarEntry::file(name, content.as_bytes().to_vec()).with_mode(0o664);
+        self.add_entry(entry);
     }
     /// Find entry by name
+    /// If the name occurs several times, the last occurrence is returned
     pub fn find_entry(&self, name: &str) -> Option<&TarEntry> {
-        self.entries.iter().find(|e| e.header.name == name)
+        self.positions(name).iter().rev().find_map(|&i| self.entry_at(i, name))
+    }
+    /// Find the n-th occurrence (starting from 1) of an entry, like `tar --occurrence`
+    pub fn find_occurrence(&self, name: &str, occurrence: usize) -> Option<&TarEntry> {
+        let i = *self.positions(name).get(occurrence.checked_sub(1)?)?;
+        self.entry_at(i, name)
+    }
+    /// Find all entries with the given name, in archive order
+    pub fn find_all(&self, name: &str) -> Vec<&TarEntry> {
+        self.positions(name).iter().filter_map(|&i| self.entry_at(i, name)).collect()
+    }
+    /// Find entry by name for modification (last occurrence)
+    /// The header is refreshed automatically when the returned guard is dropped
+    pub fn find_entry_mut(&mut self, name: &str) -> Option<TarEntryMut<'_>> {
+        self.sync_index();
+        let i = *self.positions(name).iter().rev().find(|&&i| self.entry_at(i, name).is_some())?;
+        self.tree.take();
+        Some(TarEntryMut { tar: self, index: i, old_name: name.to_string() })
+    }
+    /// Iterate over entries whose name matches a glob pattern
+    ///
+    /// `*` and `?` do not match '/', `**` matches any number of path
+    /// components and `[abc]` / `[a-z]` / `[!a-z]` match one character.
+    pub fn entries_matching<'a>(&'a self, pattern: &'a str) -> impl Iterator<Item = &'a TarEntry> + 'a {
+        self.entries.iter().filter(move |e| glob_match(pattern, &e.header.name))
+    }
+    /// Remove every entry with the given name
+    /// Returns the last removed entry, or None if no entry had that name
+    pub fn remove_entry(&mut self, name: &str) -> Option<TarEntry> {
+        self.sync_index();
+        let positions: Vec<usize> = self.positions(name).iter().copied().filter(|&i| self.entry_at(i, name).is_some()).collect();
+        let mut removed = None;
+        for &i in positions.iter().rev() {
+            let entry = self.entries.remove(i);
+            removed.get_or_insert(entry);
+        }
+        if removed.is_some() {
+            self.reindex();
+        }
+        removed
+    }
+    /// Rename an entry and refresh its header
+    /// All occurrences of `from` are renamed, keeping their order
+    /// Fails if `from` does not exist or `to` is already used by another entry
+    pub fn rename_entry(&mut self, from: &str, to: &str) -> Result<(), io::Error> {
+        self.sync_index();
+        let positions: Vec<usize> = self.positions(from).iter().copied().filter(|&i| self.entry_at(i, from).is_some()).collect();
+        if positions.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("entry not found: {}", from)));
+        }
+        if from == to {
+            return Ok(());
+        }
+        if self.find_entry(to).is_some() {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("entry already exists: {}", to)));
+        }
+        for &i in &positions {
+            let entry = &mut self.entries[i];
+            entry.header.name = to.to_string();
+            entry.refresh_header();
+        }
+        self.reindex();
+        Ok(())
+    }
+    /// Keep only the entries for which the predicate returns true
+    pub fn retain<F: FnMut(&TarEntry) -> bool>(&mut self, f: F) {
+        self.entries.retain(f);
+        self.reindex();
     }
     /// set string like key-value store
+    /// Updates the last occurrence of the name, or adds a new entry
     pub fn set_str(&mut self, name: &str, content: &str) {
-        if let Some(entry) = self.entries.iter_mut().find(|e| e.header.name == name) {
-            entry.data = content.as_bytes().to_vec();
-            entry.header.size = entry.data.len() as u64;
-            if self.use_header_parsing {
-                entry.header_bytes = entry.header.to_bytes();
-            }
-        } else {
-            self.add_str_entry(name, content);
-        }
+        self.set_bytes(name, content.as_bytes());
     }
     /// get string like key-value store
     pub fn get_str(&self, name: &str) -> Option<String> {
-        if let Some(entry) = self.entries.iter().find(|e| e.header.name == name) {
-            let data = String::from_utf8_lossy(&entry.data)
+        if let Some(entry) = self.find_entry(name) {
+            let data = String::from_utf8_lossy(&entry.data.bytes().ok()?)
                 .trim_end_matches('\0')
                 .to_string();
             Some(data)
@@ -220,13 +818,92 @@ impl Tar {
             None
         }
     }
+    /// set binary data like key-value store
+    /// New entries get mode 0o664, existing files keep their mode;
+    /// a link or directory of that name is replaced by a regular file.
+    /// mtime is set to the current time on every write
+    pub fn set_bytes(&mut self, name: &str, content: &[u8]) {
+        let mtime = now_secs();
+        if let Some(mut entry) = self.find_entry_mut(name) {
+            if entry.header.is_file() {
+                entry.data = content.to_vec().into();
+                entry.header.mtime = mtime;
+            } else {
+                *entry = TarEntry::file(name, content.to_vec()).with_mode(0o664).with_mtime(mtime);
+            }
+        } else {
+            let entry = TarEntry::file(name, content.to_vec())
+                .with_mode(0o664)
+                .with_mtime(mtime);
+            self.add_entry(entry);
+        }
+    }
+    /// get binary data like key-value store
+    /// Unlike `get_str`, the data is returned exactly as stored
+    /// Data that is not in memory is read; None is returned if that fails
+    pub fn get_bytes(&self, name: &str) -> Option<Cow<'_, [u8]>> {
+        self.find_entry(name)?.data.bytes().ok()
+    }
     /// Convert the Tar archive to bytes
-    pub fn to_bytes(&self) -> Vec<u8> {
+    /// Fails if the data of an entry cannot be read from its file
+    pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
         write_tar(&self.entries)
     }
 }
 
+#[cfg(feature = "serde")]
+impl Tar {
+    /// Serialize a value as JSON and store it like `set_bytes`
+    pub fn set_json<T: serde::Serialize + ?Sized>(&mut self, name: &str, value: &T) -> Result<(), io::Error> {
+        let data = serde_json::to_vec_pretty(value)?;
+        self.set_bytes(name, &data);
+        Ok(())
+    }
+    /// Read a value stored as JSON
+    /// Returns Ok(None) if the entry does not exist
+    pub fn get_json<T: serde::de::DeserializeOwned>(&self, name: &str) -> Result<Option<T>, io::Error> {
+        match self.get_bytes(name) {
+            Some(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(feature = "toml")]
+impl Tar {
+    /// Serialize a value as TOML and store it like `set_bytes`
+    pub fn set_toml<T: serde::Serialize + ?Sized>(&mut self, name: &str, value: &T) -> Result<(), io::Error> {
+        let text = toml::to_string(value).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        self.set_bytes(name, text.as_bytes());
+        Ok(())
+    }
+    /// Read a value stored as TOML
+    /// Returns Ok(None) if the entry does not exist
+    pub fn get_toml<T: serde::de::DeserializeOwned>(&self, name: &str) -> Result<Option<T>, io::Error> {
+        match self.get_bytes(name) {
+            Some(data) => {
+                let text = std::str::from_utf8(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                let value = toml::from_str(text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                Ok(Some(value))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+/// Current time in seconds since the Unix epoch
+pub(crate) fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
 /// Reads a tar archive from a byte slice and returns a vector of TarEntry
+///
+/// Since 0.2 entries of every type are returned, including directories and
+/// symbolic links; 0.1 returned only regular files. Filter with
+/// `entry.header.is_file()` to get the old behavior.
 pub fn read_tar(data: &[u8]) -> Vec<TarEntry> {
     let mut entries = Vec::new();
     let mut offset = 0;
@@ -256,20 +933,182 @@ pub fn read_tar(data: &[u8]) -> Vec<TarEntry> {
         let mut header_bytes = [0u8; 512];
         header_bytes.copy_from_slice(header_data);
         
-        // Only add regular files (typeflag '0' or 0)
-        if header.typeflag == b'0' || header.typeflag == 0 {
-            entries.push(TarEntry { header, data: entry_data, header_bytes });
-        }
+        // Every entry type is kept, so directories and links survive a round trip
+        entries.push(TarEntry { header, data: entry_data.into(), header_bytes });
         
         // Move to next entry (align to 512-byte boundary)
-        let padding = if size % 512 == 0 { 0 } else { 512 - (size % 512) };
+        let padding = (512 - (size % 512)) % 512;
         offset = data_end + padding;
     }
     entries
 }
 
+/// Reads the headers of an uncompressed tar file
+/// Entry data is not loaded; it refers to byte ranges of the open file.
+/// Like `read_tar`, entries of every type are returned (since 0.2).
+pub fn read_tar_file<P: AsRef<Path>>(path: P) -> Result<Vec<TarEntry>, io::Error> {
+    use std::os::unix::fs::FileExt;
+
+    let path = path.as_ref();
+    let compression = Compression::detect_file(path)?;
+    if compression != Compression::None {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("cannot read entries lazily from a {} archive: {}", compression.name(), path.display()),
+        ));
+    }
+    let file = Arc::new(File::open(path)?);
+    let len = file.metadata()?.len();
+    let mut entries = Vec::new();
+    let mut offset = 0u64;
+    let mut header_bytes = [0u8; 512];
+    while offset + 512 <= len {
+        file.read_exact_at(&mut header_bytes, offset)?;
+        if is_empty_block(&header_bytes) {
+            break;
+        }
+        let header = parse_tar_header(&header_bytes);
+        let data_start = offset + 512;
+        if data_start + header.size > len {
+            break; // Corrupted archive
+        }
+        let size = header.size;
+        let data = EntryData::from_file_range(Arc::clone(&file), data_start, size);
+        entries.push(TarEntry { header, data, header_bytes });
+        offset = data_start + size.div_ceil(512) * 512;
+    }
+    Ok(entries)
+}
+
+/// Streaming reader over a tar stream
+///
+/// Headers are read one at a time and the reader itself yields the data of
+/// the current entry, so archives of any size are processed with bounded
+/// memory. Data left unread is skipped by the next `next_entry` call.
+///
+/// ```rust
+/// use std::io::Read;
+/// use tar_light::{Tar, TarReader};
+///
+/// let mut tar = Tar::new();
+/// tar.add_str_entry("hello.txt", "Hello");
+/// let data = tar.to_bytes().unwrap();
+///
+/// let mut reader = TarReader::new(&data[..]);
+/// while let Some(header) = reader.next_entry().unwrap() {
+///     let mut content = String::new();
+///     reader.read_to_string(&mut content).unwrap();
+///     assert_eq!((header.name.as_str(), content.as_str()), ("hello.txt", "Hello"));
+/// }
+/// ```
+pub struct TarReader<R: Read> {
+    reader: R,
+    header_bytes: [u8; 512],
+    /// Unread data of the current entry
+    remaining: u64,
+    /// Padding after the data of the current entry
+    padding: u64,
+    done: bool,
+}
+
+impl<R: Read> TarReader<R> {
+    /// Read tar entries from an uncompressed stream
+    pub fn new(reader: R) -> Self {
+        Self { reader, header_bytes: [0; 512], remaining: 0, padding: 0, done: false }
+    }
+
+    /// Advance to the next entry and return its header
+    /// Returns None at the end-of-archive marker or the end of the stream
+    pub fn next_entry(&mut self) -> Result<Option<TarHeader>, io::Error> {
+        if self.done {
+            return Ok(None);
+        }
+        let skip = self.remaining + self.padding;
+        if io::copy(&mut (&mut self.reader).take(skip), &mut io::sink())? < skip {
+            return Err(truncated());
+        }
+        self.remaining = 0;
+        self.padding = 0;
+
+        let mut filled = 0;
+        while filled < 512 {
+            match self.reader.read(&mut self.header_bytes[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        // like read_tar, a missing end marker or a partial block ends the archive
+        if filled < 512 || is_empty_block(&self.header_bytes) {
+            self.done = true;
+            return Ok(None);
+        }
+        let header = parse_tar_header(&self.header_bytes);
+        self.remaining = header.size;
+        self.padding = header.size.div_ceil(512) * 512 - header.size;
+        Ok(Some(header))
+    }
+
+    /// Raw bytes of the current header
+    pub fn header_bytes(&self) -> &[u8; 512] {
+        &self.header_bytes
+    }
+
+    /// Return the underlying reader
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+/// Reads the data of the current entry
+impl<R: Read> Read for TarReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let want = buf.len().min(self.remaining.min(usize::MAX as u64) as usize);
+        if want == 0 {
+            return Ok(0);
+        }
+        let n = self.reader.read(&mut buf[..want])?;
+        if n == 0 {
+            return Err(truncated());
+        }
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+fn truncated() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "truncated archive: entry data runs past the end of the stream")
+}
+
+/// Find the offset of the end-of-archive marker by walking the headers
+/// Returns the file length if the archive has no end marker
+pub(crate) fn find_end_of_archive<R: Read + Seek>(reader: &mut R) -> Result<u64, io::Error> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(0))?;
+    let mut offset = 0u64;
+    let mut block = [0u8; 512];
+    while offset + 512 <= len {
+        reader.read_exact(&mut block)?;
+        if is_empty_block(&block) {
+            return Ok(offset);
+        }
+        let header = parse_tar_header(&block);
+        let next = offset + 512 + header.size.div_ceil(512) * 512;
+        if next > len {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("truncated archive: entry {} runs past the end of the file", header.name),
+            ));
+        }
+        reader.seek(SeekFrom::Start(next))?;
+        offset = next;
+    }
+    Ok(offset)
+}
+
 /// Check if a block is empty (all zeros)
-fn is_empty_block(data: &[u8]) -> bool {
+pub(crate) fn is_empty_block(data: &[u8]) -> bool {
     data.iter().all(|&b| b == 0)
 }
 
@@ -301,7 +1140,7 @@ fn read_tar_u64(data: &[u8], range: Range<usize>) -> u64 {
 fn read_tar_checksum(data: &[u8], range: Range<usize>) -> u32 {
     // checksum is stored as octal string
     // e.g., "0000644\0 "=(str + null + space)
-    let s = read_tar_str(&data, range)
+    let s = read_tar_str(data, range)
         .trim()
         .trim_end_matches('\0')
         .trim()
@@ -312,7 +1151,7 @@ fn read_tar_checksum(data: &[u8], range: Range<usize>) -> u32 {
     u32::from_str_radix(&s, 8).unwrap_or(0)
 }
 
-fn parse_tar_header(data: &[u8]) -> TarHeader {
+pub(crate) fn parse_tar_header(data: &[u8]) -> TarHeader {
     // Simplified parsing logic for demonstration purposes
     TarHeader {
         name: read_tar_str(data, 0..100),
@@ -335,20 +1174,53 @@ fn parse_tar_header(data: &[u8]) -> TarHeader {
 }
 
 /// Writes a vector of TarEntry to a tar archive in a byte vector
-pub fn write_tar(entries: &[TarEntry]) -> Vec<u8> {
+/// Fails if the data of an entry cannot be read from its file
+pub fn write_tar(entries: &[TarEntry]) -> Result<Vec<u8>, io::Error> {
     let mut tar_data = Vec::new();
+    write_tar_to(entries, &mut tar_data)?;
+    Ok(tar_data)
+}
+
+/// Writes entries as a tar archive to a writer
+/// Entry data is streamed from its source one entry at a time
+pub fn write_tar_to<W: Write + ?Sized>(entries: &[TarEntry], writer: &mut W) -> Result<(), io::Error> {
     for entry in entries {
-        // Use header_bytes if available, otherwise create from header
-        let header_bytes = create_tar_header(&entry.header);
-        tar_data.extend_from_slice(&header_bytes);
-        // Write data and padding to 512-byte boundary
-        tar_data.extend_from_slice(&entry.data);
-        let padding = (512 - (entry.data.len() % 512)) % 512;
-        tar_data.extend_from_slice(&vec![0u8; padding]);
+        write_tar_entry(entry, writer)?;
     }
     // Add two 512-byte zero blocks at the end (TAR format specification)
-    tar_data.extend_from_slice(&[0u8; 1024]);
-    tar_data
+    writer.write_all(&[0u8; 1024])
+}
+
+/// Writes entries through a compressor, marking every entry boundary
+/// so seekable formats can start a new frame there
+pub(crate) fn write_tar_encoded<W: Write>(
+    entries: &[TarEntry],
+    encoder: &mut compression::Encoder<W>,
+) -> Result<(), io::Error> {
+    for entry in entries {
+        write_tar_entry(entry, encoder)?;
+        encoder.end_frame()?;
+    }
+    encoder.write_all(&[0u8; 1024])
+}
+
+/// Writes one entry: header, data and padding to a 512-byte boundary
+/// Only regular files have data, and exactly `header.size` bytes are written,
+/// so the stream always matches its headers
+fn write_tar_entry<W: Write + ?Sized>(entry: &TarEntry, writer: &mut W) -> Result<(), io::Error> {
+    let size = if entry.header.is_file() { entry.data.len() } else { 0 };
+    if size != entry.header.size {
+        return Err(invalid_input(format!(
+            "{}: header size {} does not match the {} bytes of data",
+            entry.header.name, entry.header.size, size
+        )));
+    }
+    writer.write_all(&create_tar_header(&entry.header))?;
+    if size > 0 {
+        entry.data.write_to(writer)?;
+    }
+    let padding = (512 - (size % 512)) % 512;
+    writer.write_all(&[0u8; 512][..padding as usize])
 }
 
 fn create_tar_header(header: &TarHeader) -> [u8; 512] {
@@ -466,7 +1338,7 @@ mod tests {
         tar.add_str_entry("foo.txt", "hello");
         assert_eq!(tar.entries.len(), 1);
         assert_eq!(tar.entries[0].header.name, "foo.txt");
-        assert_eq!(String::from_utf8_lossy(&tar.entries[0].data), "hello");
+        assert_eq!(String::from_utf8_lossy(tar.entries[0].data.as_bytes().unwrap()), "hello");
 
         // set_str で同名エントリを上書き
         tar.set_str("foo.txt", "world");
@@ -477,7 +1349,7 @@ mod tests {
         let header = TarHeader::new("bar.txt".to_string(), 0o644, 3);
         let data = b"abc".to_vec();
         let header_bytes = header.to_bytes();
-        let entry = TarEntry { header, data: data.clone(), header_bytes };
+        let entry = TarEntry { header, data: data.clone().into(), header_bytes };
         tar.add_entry(entry);
         assert_eq!(tar.entries.len(), 2);
         assert_eq!(tar.get_str("bar.txt").as_deref(), Some("abc"));
@@ -485,13 +1357,13 @@ mod tests {
         // find_entry で検索
         let found = tar.find_entry("foo.txt");
         assert!(found.is_some());
-        assert_eq!(String::from_utf8_lossy(&found.unwrap().data), "world");
+        assert_eq!(String::from_utf8_lossy(found.unwrap().data.as_bytes().unwrap()), "world");
 
         // to_bytes でバイト列化し from_bytes で復元
         let mut tar1  = Tar::new();
         tar1.add_str_entry("foo.txt", "foo");
         tar1.add_str_entry("bar.txt", "bar");
-        let bytes = tar1.to_bytes();
+        let bytes = tar1.to_bytes().unwrap();
         println!("Bytes length: {}", bytes.len());
         let tar2 = Tar::from_bytes(&bytes);
         println!("Tar2: {:?}", tar2.entries);
@@ -513,7 +1385,7 @@ mod tests {
         let test_entry = test_entry.unwrap();
         assert_eq!(test_entry.header.name, "test.txt");
         assert_eq!(test_entry.header.size, 33);
-        assert_eq!(String::from_utf8_lossy(&test_entry.data), "0123456789ABCDEF__This is a pen.\n");
+        assert_eq!(String::from_utf8_lossy(test_entry.data.as_bytes().unwrap()), "0123456789ABCDEF__This is a pen.\n");
     }
 
     #[test]
@@ -528,10 +1400,10 @@ mod tests {
         // Create a test entry
         let data = b"Hello, World".to_vec();
         let header_bytes = header.to_bytes();
-        entries.push(TarEntry { header, data, header_bytes });
+        entries.push(TarEntry { header, data: data.into(), header_bytes });
         
         // Write tar and verify it can be read back
-        let tar_data = write_tar(&entries);
+        let tar_data = write_tar(&entries).unwrap();
         let read_entries = read_tar(&tar_data);
         
         assert_eq!(read_entries.len(), 1);
@@ -554,8 +1426,8 @@ mod tests {
         
         // Create entry and verify it can be read back
         let data = b"Test data!".to_vec();
-        let entry = TarEntry { header, data: data.clone(), header_bytes };
-        let tar_data = write_tar(&[entry]);
+        let entry = TarEntry { header, data: data.clone().into(), header_bytes };
+        let tar_data = write_tar(&[entry]).unwrap();
         let read_entries = read_tar(&tar_data);
         
         assert_eq!(read_entries.len(), 1);
@@ -579,8 +1451,8 @@ mod tests {
         
         // Create entry and verify it can be read back
         let data = b"Test data!".to_vec();
-        let entry = TarEntry { header, data: data.clone(), header_bytes };
-        let tar_data = write_tar(&[entry]);
+        let entry = TarEntry { header, data: data.clone().into(), header_bytes };
+        let tar_data = write_tar(&[entry]).unwrap();
         let read_entries = read_tar(&tar_data);
         
         assert_eq!(read_entries.len(), 1);
@@ -598,8 +1470,8 @@ mod tests {
         
         // Create entry and verify it can be read back
         let data = b"Hello".to_vec();
-        let entry = TarEntry { header, data: data.clone(), header_bytes };
-        let tar_data = write_tar(&[entry]);
+        let entry = TarEntry { header, data: data.clone().into(), header_bytes };
+        let tar_data = write_tar(&[entry]).unwrap();
         let read_entries = read_tar(&tar_data);
         
         assert_eq!(read_entries.len(), 1);
@@ -638,8 +1510,8 @@ mod tests {
         assert!(header_bytes[345..500].iter().any(|&b| b != 0), "Prefix field should have data");
         
         // Create entry and verify it can be written and read
-        let entry = TarEntry { header: header_with_prefix, data: data.clone(), header_bytes };
-        let tar_data = write_tar(&[entry]);
+        let entry = TarEntry { header: header_with_prefix, data: data.clone().into(), header_bytes };
+        let tar_data = write_tar(&[entry]).unwrap();
         let read_entries = read_tar(&tar_data);
         
         assert_eq!(read_entries.len(), 1);
@@ -662,8 +1534,8 @@ mod tests {
             let data = b"malicious!".to_vec();
             let header_bytes = header.to_bytes();
             
-            let entry = TarEntry { header, data: data.clone(), header_bytes };
-            let tar_data = write_tar(&[entry]);
+            let entry = TarEntry { header, data: data.clone().into(), header_bytes };
+            let tar_data = write_tar(&[entry]).unwrap();
             let read_entries = read_tar(&tar_data);
             
             // Archive should be parseable
@@ -684,10 +1556,12 @@ mod tests {
         
         let entry = TarEntry { 
             header, 
-            data: data.clone(), 
+            data: data.clone().into(), 
             header_bytes 
         };
-        let tar_data = write_tar(&[entry]);
+        // The writer refuses to produce such an archive
+        assert_eq!(write_tar(&[entry]).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        let tar_data = [&header_bytes[..], &data, &[0u8; 508 + 1024]].concat();
         
         // read_tar should handle this gracefully (reads only what's available)
         let read_entries = read_tar(&tar_data);
@@ -704,8 +1578,9 @@ mod tests {
         let data = b"small".to_vec();
         let header_bytes = header.to_bytes();
         
-        let entry = TarEntry { header, data, header_bytes };
-        let tar_data = write_tar(&[entry]);
+        let entry = TarEntry { header, data: data.clone().into(), header_bytes };
+        assert_eq!(write_tar(&[entry]).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        let tar_data = [&header_bytes[..], &data, &[0u8; 507 + 1024]].concat();
         
         // read_tar should not crash or allocate massive memory
         let read_entries = read_tar(&tar_data);
@@ -728,8 +1603,8 @@ mod tests {
             let data = b"test!".to_vec();
             let header_bytes = header.to_bytes();
             
-            let entry = TarEntry { header, data: data.clone(), header_bytes };
-            let tar_data = write_tar(&[entry]);
+            let entry = TarEntry { header, data: data.clone().into(), header_bytes };
+            let tar_data = write_tar(&[entry]).unwrap();
             let read_entries = read_tar(&tar_data);
             
             assert_eq!(read_entries.len(), 1);
@@ -743,7 +1618,7 @@ mod tests {
         let mut tar = Tar::new();
         tar.use_header_parsing = true;
         tar.add_str_entry("test.txt", "test data!");
-        let mut tar_data = tar.to_bytes();
+        let mut tar_data = tar.to_bytes().unwrap();
         tar_data[148] = b'9'; // Corrupt checksum
         tar_data[149] = b'9'; // Corrupt checksum
         let tar2 = Tar::from_bytes(&tar_data);
@@ -761,14 +1636,17 @@ mod tests {
         
         let entry = TarEntry { 
             header, 
-            data: Vec::new(), 
+            data: Vec::new().into(), 
             header_bytes 
         };
-        let tar_data = write_tar(&[entry]);
+        let tar_data = write_tar(&[entry]).unwrap();
         let read_entries = read_tar(&tar_data);
         
-        // Symbolic links should be filtered out (only regular files returned)
-        assert_eq!(read_entries.len(), 0);
+        // Symbolic links are kept as links; their target is never read
+        assert_eq!(read_entries.len(), 1);
+        assert!(read_entries[0].header.is_symlink());
+        assert_eq!(read_entries[0].header.linkname, "/etc/passwd");
+        assert!(read_entries[0].data.is_empty());
     }
 
     #[test]
@@ -788,14 +1666,16 @@ mod tests {
             
             let entry = TarEntry { 
                 header, 
-                data: Vec::new(), 
+                data: Vec::new().into(), 
                 header_bytes 
             };
-            let tar_data = write_tar(&[entry]);
+            let tar_data = write_tar(&[entry]).unwrap();
             let read_entries = read_tar(&tar_data);
             
-            // Non-regular files should be filtered out
-            assert_eq!(read_entries.len(), 0, "Typeflag {} should be filtered", typeflag);
+            // Non-regular entries are kept without data
+            assert_eq!(read_entries.len(), 1, "Typeflag {} should be kept", typeflag);
+            assert_eq!(read_entries[0].header.typeflag, typeflag);
+            assert!(read_entries[0].data.is_empty());
         }
     }
 
@@ -807,8 +1687,8 @@ mod tests {
         let data = b"deep".to_vec();
         let header_bytes = header.to_bytes();
         
-        let entry = TarEntry { header, data: data.clone(), header_bytes };
-        let tar_data = write_tar(&[entry]);
+        let entry = TarEntry { header, data: data.clone().into(), header_bytes };
+        let tar_data = write_tar(&[entry]).unwrap();
         let read_entries = read_tar(&tar_data);
         
         assert_eq!(read_entries.len(), 1);
@@ -836,4 +1716,436 @@ mod tests {
         // May return empty or incomplete entry, but shouldn't crash
         assert!(read_entries.is_empty() || read_entries[0].data.len() <= 5);
     }
+
+    #[test]
+    fn header_builder_test() {
+        let header = TarHeader::builder()
+            .name("bin/tool")
+            .mode(0o755)
+            .size(42)
+            .mtime(1_700_000_000)
+            .uname("alice")
+            .build()
+            .unwrap();
+        assert_eq!(header.name, "bin/tool");
+        assert_eq!(header.mode, 0o755);
+        assert_eq!(header.size, 42);
+        assert!(header.is_file());
+        assert!(header.verify_checksum(&header.to_bytes()));
+        assert_eq!(TarHeader::from_bytes(&header.to_bytes()).checksum, header.checksum);
+
+        // Oversized and inconsistent fields are rejected
+        assert!(TarHeader::builder().build().is_err());
+        assert!(TarHeader::builder().name(&"a".repeat(101)).build().is_err());
+        assert!(TarHeader::builder().name("x").uname(&"u".repeat(33)).build().is_err());
+        assert!(TarHeader::builder().name("x").size(u64::MAX).build().is_err());
+        assert!(TarHeader::builder().name("x").typeflag(TYPE_SYMLINK).build().is_err());
+        assert!(TarHeader::builder().name("d/").typeflag(TYPE_DIRECTORY).size(1).build().is_err());
+        assert!(TarHeader::builder().name("d").typeflag(TYPE_DIRECTORY).build().is_err());
+    }
+
+    #[test]
+    fn entry_constructors_test() {
+        let file = TarEntry::file("a.txt", b"hello".to_vec());
+        assert_eq!(file.header.size, 5);
+        assert_eq!(file.header.typeflag, TYPE_REGULAR);
+        assert!(file.header.verify_checksum(&file.header_bytes));
+        assert_eq!(file.header_bytes, file.header.to_bytes());
+
+        let dir = TarEntry::directory("docs");
+        assert_eq!(dir.header.name, "docs/");
+        assert!(dir.header.is_dir());
+        assert_eq!(dir.header.mode, 0o755);
+
+        let link = TarEntry::symlink("latest", "a.txt");
+        assert!(link.header.is_symlink());
+        assert_eq!(link.header.linkname, "a.txt");
+        assert_eq!(link.header.size, 0);
+
+        let hard = TarEntry::hardlink("b.txt", "a.txt");
+        assert!(hard.header.is_hardlink());
+
+        let fifo = TarEntry::fifo("pipe");
+        assert_eq!(fifo.header.typeflag, TYPE_FIFO);
+
+        let chr = TarEntry::char_device("null", 1, 3);
+        assert_eq!(chr.header.typeflag, TYPE_CHAR_DEVICE);
+        let parsed = TarHeader::from_bytes(&chr.header_bytes);
+        assert_eq!((parsed.devmajor, parsed.devminor), (1, 3));
+
+        let blk = TarEntry::block_device("sda", 8, 0);
+        assert_eq!(blk.header.typeflag, TYPE_BLOCK_DEVICE);
+        assert!(blk.header.verify_checksum(&blk.header_bytes));
+
+        // with_mode keeps header bytes in sync
+        let file = file.with_mode(0o600);
+        assert_eq!(TarHeader::from_bytes(&file.header_bytes).mode, 0o600);
+    }
+
+    #[test]
+    fn add_str_entry_header_bytes_test() {
+        // header_bytes are filled even without use_header_parsing
+        let mut tar = Tar::new();
+        tar.add_str_entry("k.txt", "value");
+        let entry = &tar.entries[0];
+        assert_eq!(entry.header_bytes, entry.header.to_bytes());
+        tar.set_str("k.txt", "longer value");
+        let entry = &tar.entries[0];
+        assert_eq!(TarHeader::from_bytes(&entry.header_bytes).size, 12);
+    }
+
+    #[test]
+    fn tar_edit_methods_test() {
+        let mut tar = Tar::new();
+        tar.add_str_entry("src/main.rs", "fn main() {}");
+        tar.add_str_entry("src/lib.rs", "pub mod tar;");
+        tar.add_str_entry("README.md", "# readme");
+
+        // find_entry_mut refreshes the header when the guard is dropped
+        {
+            let mut entry = tar.find_entry_mut("README.md").unwrap();
+            entry.data = b"# tar_light".to_vec().into();
+        }
+        let entry = tar.find_entry("README.md").unwrap();
+        assert_eq!(entry.header.size, 11);
+        assert!(entry.header.verify_checksum(&entry.header_bytes));
+        assert!(tar.find_entry_mut("missing").is_none());
+
+        // rename_entry
+        tar.rename_entry("README.md", "docs/README.md").unwrap();
+        assert!(tar.find_entry("README.md").is_none());
+        let entry = tar.find_entry("docs/README.md").unwrap();
+        assert_eq!(TarHeader::from_bytes(&entry.header_bytes).name, "docs/README.md");
+        assert!(tar.rename_entry("missing", "x").is_err());
+        assert!(tar.rename_entry("src/lib.rs", "src/main.rs").is_err());
+
+        // entries_matching
+        let names: Vec<&str> = tar.entries_matching("src/*.rs").map(|e| e.header.name.as_str()).collect();
+        assert_eq!(names, vec!["src/main.rs", "src/lib.rs"]);
+        assert_eq!(tar.entries_matching("*.md").count(), 0);
+        assert_eq!(tar.entries_matching("**/*.md").count(), 1);
+
+        // remove_entry
+        let removed = tar.remove_entry("src/main.rs").unwrap();
+        assert_eq!(removed.data, b"fn main() {}");
+        assert!(tar.remove_entry("src/main.rs").is_none());
+        assert_eq!(tar.entries.len(), 2);
+
+        // retain
+        tar.retain(|e| !e.header.name.starts_with("docs/"));
+        assert_eq!(tar.entries.len(), 1);
+        assert_eq!(tar.entries[0].header.name, "src/lib.rs");
+    }
+
+    #[test]
+    fn glob_match_test() {
+        assert!(glob_match("*.txt", "a.txt"));
+        assert!(!glob_match("*.txt", "dir/a.txt"));
+        assert!(glob_match("**/*.txt", "a.txt"));
+        assert!(glob_match("**/*.txt", "dir/sub/a.txt"));
+        assert!(glob_match("dir/**", "dir/sub/a.txt"));
+        assert!(glob_match("file?.txt", "file1.txt"));
+        assert!(!glob_match("file?.txt", "file10.txt"));
+        assert!(glob_match("file[0-9].txt", "file7.txt"));
+        assert!(!glob_match("file[!0-9].txt", "file7.txt"));
+        assert!(glob_match("file[!0-9].txt", "fileA.txt"));
+        assert!(glob_match("exact", "exact"));
+        assert!(!glob_match("exact", "exactly"));
+    }
+
+    #[test]
+    fn tar_duplicate_names_test() {
+        let entries = vec![
+            TarEntry::file("config.txt", b"v1".to_vec()),
+            TarEntry::file("other.txt", b"other".to_vec()),
+            TarEntry::file("config.txt", b"v2".to_vec()),
+        ];
+        let tar = Tar::from_bytes(&write_tar(&entries).unwrap());
+
+        // The last occurrence wins, as in GNU tar
+        assert_eq!(tar.get_str("config.txt").as_deref(), Some("v2"));
+        assert_eq!(tar.find_entry("config.txt").unwrap().data, b"v2");
+        assert_eq!(tar.find_occurrence("config.txt", 1).unwrap().data, b"v1");
+        assert_eq!(tar.find_occurrence("config.txt", 2).unwrap().data, b"v2");
+        assert!(tar.find_occurrence("config.txt", 0).is_none());
+        assert!(tar.find_occurrence("config.txt", 3).is_none());
+        let all: Vec<&[u8]> = tar.find_all("config.txt").iter().map(|e| e.data.as_bytes().unwrap()).collect();
+        assert_eq!(all, vec![b"v1".as_slice(), b"v2".as_slice()]);
+
+        // set_str updates the authoritative (last) copy
+        let mut tar = tar;
+        tar.set_str("config.txt", "v3");
+        assert_eq!(tar.find_occurrence("config.txt", 1).unwrap().data, b"v1");
+        assert_eq!(tar.get_str("config.txt").as_deref(), Some("v3"));
+    }
+
+    #[test]
+    fn tar_index_direct_modification_test() {
+        let mut tar = Tar::new();
+        tar.add_str_entry("a.txt", "a");
+        tar.add_str_entry("b.txt", "b");
+
+        // Pushes and removals through `entries` are picked up without reindex()
+        tar.entries.push(TarEntry::file("e.txt", b"pushed".to_vec()));
+        assert_eq!(tar.get_str("e.txt").as_deref(), Some("pushed"));
+        tar.set_str("e.txt", "set");
+        assert_eq!(tar.find_all("e.txt").len(), 1);
+        assert_eq!(tar.get_str("e.txt").as_deref(), Some("set"));
+        tar.entries.pop();
+        assert!(tar.find_entry("e.txt").is_none());
+        tar.entries.remove(0);
+        assert!(tar.find_entry("a.txt").is_none());
+        assert!(tar.find_all("a.txt").is_empty());
+        assert!(tar.find_entry_mut("a.txt").is_none());
+        assert!(tar.remove_entry("a.txt").is_none());
+        assert_eq!(tar.get_str("b.txt").as_deref(), Some("b"));
+
+        // Renaming through `entries` requires reindex()
+        tar.entries[0].header.name = "c.txt".to_string();
+        tar.reindex();
+        assert_eq!(tar.get_str("c.txt").as_deref(), Some("b"));
+        assert!(tar.find_entry("b.txt").is_none());
+        tar.add_str_entry("d.txt", "d");
+        assert_eq!(tar.find_all("d.txt").len(), 1);
+        assert_eq!(tar.entries.len(), 2);
+    }
+
+    #[test]
+    fn tar_bytes_test() {
+        let mut tar = Tar::new();
+        let blob = vec![0u8, 1, 2, 255, 0, 0];
+        tar.set_bytes("blob.bin", &blob);
+        // Trailing NULs are kept, unlike get_str
+        assert_eq!(tar.get_bytes("blob.bin").as_deref(), Some(blob.as_slice()));
+        assert!(tar.get_bytes("missing").is_none());
+
+        let entry = tar.find_entry("blob.bin").unwrap();
+        assert_eq!(entry.header.mode, 0o664);
+        assert!(entry.header.mtime > 0);
+
+        // Updating keeps the mode but refreshes size and mtime
+        tar.find_entry_mut("blob.bin").unwrap().header.mode = 0o600;
+        tar.set_bytes("blob.bin", b"\0\0");
+        let entry = tar.find_entry("blob.bin").unwrap();
+        assert_eq!(entry.header.mode, 0o600);
+        assert_eq!(entry.header.size, 2);
+
+        // Round trip through bytes
+        let tar2 = Tar::from_bytes(&tar.to_bytes().unwrap());
+        assert_eq!(tar2.get_bytes("blob.bin").as_deref(), Some(&b"\0\0"[..]));
+
+        // A value written over a link or directory replaces it with a file
+        let mut tar = Tar::new();
+        tar.add_entry(TarEntry::symlink("link", "blob.bin"));
+        tar.add_entry(TarEntry::directory("dir"));
+        tar.set_str("link", "data");
+        tar.set_bytes("dir/", b"more");
+        let tar2 = Tar::from_bytes(&tar.to_bytes().unwrap());
+        let read: Vec<(&str, bool, u64)> =
+            tar2.entries.iter().map(|e| (e.header.name.as_str(), e.header.is_file(), e.header.size)).collect();
+        assert_eq!(read, vec![("link", true, 4), ("dir/", true, 4)]);
+        assert_eq!(tar2.get_str("link").as_deref(), Some("data"));
+        assert_eq!(tar2.find_entry("link").unwrap().header.linkname, "");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn tar_json_test() {
+        #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+        struct Config {
+            name: String,
+            retries: u32,
+            tags: Vec<String>,
+        }
+        let config = Config { name: "build".to_string(), retries: 3, tags: vec!["ci".to_string()] };
+        let mut tar = Tar::new();
+        tar.set_json("config.json", &config).unwrap();
+        let tar = Tar::from_bytes(&tar.to_bytes().unwrap());
+        assert_eq!(tar.get_json::<Config>("config.json").unwrap(), Some(config));
+        assert_eq!(tar.get_json::<Config>("missing.json").unwrap(), None);
+
+        let mut tar = tar;
+        tar.set_str("broken.json", "{not json");
+        assert!(tar.get_json::<Config>("broken.json").is_err());
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn tar_toml_test() {
+        #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+        struct Settings {
+            title: String,
+            level: i64,
+        }
+        let settings = Settings { title: "tar".to_string(), level: 9 };
+        let mut tar = Tar::new();
+        tar.set_toml("settings.toml", &settings).unwrap();
+        assert!(tar.get_str("settings.toml").unwrap().contains("level = 9"));
+        assert_eq!(tar.get_toml::<Settings>("settings.toml").unwrap(), Some(settings));
+    }
+
+    #[test]
+    fn tar_open_save_test() {
+        let mut tar = Tar::new();
+        tar.add_str_entry("a.txt", "alpha");
+        tar.set_bytes("b.bin", &[1, 0, 2]);
+
+        for path in ["test_tar_open_save.tar", "test_tar_open_save.tar.gz"] {
+            tar.save(path).unwrap();
+            let gzipped = std::fs::read(path).unwrap().starts_with(&[0x1f, 0x8b]);
+            assert_eq!(gzipped, path.ends_with(".gz"));
+            let loaded = Tar::open(path).unwrap();
+            assert_eq!(loaded.get_str("a.txt").as_deref(), Some("alpha"));
+            assert_eq!(loaded.get_bytes("b.bin").as_deref(), Some(&[1u8, 0, 2][..]));
+            std::fs::remove_file(path).unwrap();
+        }
+
+        // Compression is detected from content, not the file name
+        let path = "test_tar_open_misnamed.tar";
+        tar.save("test_tar_open_misnamed.tgz").unwrap();
+        std::fs::rename("test_tar_open_misnamed.tgz", path).unwrap();
+        assert_eq!(Tar::open(path).unwrap().entries.len(), 2);
+        std::fs::remove_file(path).unwrap();
+
+        assert!(Tar::open("no_such_archive.tar").is_err());
+    }
+
+    #[test]
+    fn tar_open_save_keeps_every_entry_type_test() {
+        let mut tar = Tar::new();
+        tar.add_entry(TarEntry::directory("docs").with_mode(0o700).with_mtime(1_000));
+        tar.add_str_entry("docs/a.txt", "alpha");
+        tar.add_entry(TarEntry::symlink("latest", "docs/a.txt"));
+
+        for path in ["test_tar_open_types.tar", "test_tar_open_types.tar.gz"] {
+            tar.save(path).unwrap();
+            let mut loaded = vec![Tar::open(path).unwrap()];
+            if path.ends_with(".tar") {
+                loaded.push(Tar::open_lazy(path).unwrap());
+            }
+            for loaded in loaded {
+                let names: Vec<&str> = loaded.entries.iter().map(|e| e.header.name.as_str()).collect();
+                assert_eq!(names, vec!["docs/", "docs/a.txt", "latest"]);
+                let dir = &loaded.entries[0].header;
+                assert!(dir.is_dir());
+                assert_eq!((dir.mode, dir.mtime), (0o700, 1_000));
+                let link = &loaded.entries[2].header;
+                assert!(link.is_symlink());
+                assert_eq!(link.linkname, "docs/a.txt");
+
+                // a second round trip gives the same bytes
+                loaded.save("test_tar_open_types_copy.tar").unwrap();
+                assert_eq!(std::fs::read("test_tar_open_types_copy.tar").unwrap(), tar.to_bytes().unwrap());
+            }
+            std::fs::remove_file(path).unwrap();
+        }
+        std::fs::remove_file("test_tar_open_types_copy.tar").unwrap();
+    }
+
+    #[test]
+    fn find_end_of_archive_test() {
+        let entries = vec![TarEntry::file("a.txt", b"hello".to_vec()), TarEntry::file("b.txt", vec![7u8; 600])];
+        let data = write_tar(&entries).unwrap();
+        let mut cursor = std::io::Cursor::new(&data);
+        assert_eq!(find_end_of_archive(&mut cursor).unwrap(), 512 + 512 + 512 + 1024);
+
+        // missing end marker
+        let mut cursor = std::io::Cursor::new(&data[..2560]);
+        assert_eq!(find_end_of_archive(&mut cursor).unwrap(), 2560);
+
+        // truncated data
+        let mut cursor = std::io::Cursor::new(&data[..1536]);
+        assert!(find_end_of_archive(&mut cursor).is_err());
+    }
+
+    #[test]
+    fn tar_open_lazy_test() {
+        let path = "test_tar_open_lazy.tar";
+        let mut tar = Tar::new();
+        tar.add_str_entry("a.txt", "alpha");
+        tar.add_entry(TarEntry::file("b.bin", vec![7u8; 1000]));
+        tar.add_entry(TarEntry::symlink("link", "a.txt"));
+        tar.save(path).unwrap();
+
+        let mut lazy = Tar::open_lazy(path).unwrap();
+        assert_eq!(lazy.entries.len(), 3);
+        assert!(!lazy.entries[1].data.is_loaded());
+        assert_eq!(lazy.entries[1].data.len(), 1000);
+        assert_eq!(lazy.get_str("a.txt").as_deref(), Some("alpha"));
+
+        // saving over the backing file streams from the old file
+        lazy.set_str("c.txt", "gamma");
+        lazy.save(path).unwrap();
+        assert_eq!(lazy.find_entry("b.bin").unwrap().data, vec![7u8; 1000]);
+        let saved = Tar::open(path).unwrap();
+        assert_eq!(saved.entries.len(), 4);
+        assert_eq!(saved.find_entry("b.bin").unwrap().data, vec![7u8; 1000]);
+        assert_eq!(saved.to_bytes().unwrap(), lazy.to_bytes().unwrap());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn tar_reader_test() {
+        let mut tar = Tar::new();
+        tar.add_str_entry("a.txt", "alpha");
+        tar.add_entry(TarEntry::symlink("link", "a.txt"));
+        tar.add_entry(TarEntry::file("big.bin", vec![7u8; 1500]));
+        tar.add_str_entry("c.txt", "gamma");
+        let data = tar.to_bytes().unwrap();
+
+        let mut reader = TarReader::new(&data[..]);
+        let mut seen = Vec::new();
+        while let Some(header) = reader.next_entry().unwrap() {
+            // read part of big.bin only; the rest is skipped
+            let mut content = vec![0u8; header.size.min(10) as usize];
+            reader.read_exact(&mut content).unwrap();
+            assert_eq!(reader.header_bytes()[..100], header.to_bytes()[..100]);
+            seen.push((header.name, content));
+        }
+        assert_eq!(seen.len(), 4);
+        assert_eq!(seen[2], ("big.bin".to_string(), vec![7u8; 10]));
+        assert_eq!(seen[3], ("c.txt".to_string(), b"gamma".to_vec()));
+        assert!(reader.next_entry().unwrap().is_none());
+
+        // a stream cut inside entry data is an error, not a short entry
+        let mut reader = TarReader::new(&data[..512 + 512 + 512 + 700]);
+        reader.next_entry().unwrap();
+        reader.next_entry().unwrap();
+        reader.next_entry().unwrap();
+        let err = io::copy(&mut reader, &mut io::sink()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(reader.next_entry().unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn to_bytes_unreadable_data_test() {
+        let path = "test_to_bytes_unreadable.bin";
+        std::fs::write(path, b"data").unwrap();
+        let mut tar = Tar::new();
+        tar.add_entry(TarEntry::file("a.bin", EntryData::from_path(path).unwrap()));
+        assert_eq!(tar.to_bytes().unwrap().len(), 512 + 512 + 1024);
+
+        // a file removed since the entry was created is an error, not a panic
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(tar.to_bytes().unwrap_err().kind(), io::ErrorKind::NotFound);
+        assert!(write_tar(&tar.entries).is_err());
+    }
+
+    #[test]
+    fn non_regular_entries_have_no_data_test() {
+        let mut header = TarHeader::new("d/".to_string(), 0o755, 0);
+        header.typeflag = TYPE_DIRECTORY;
+        let entry = TarEntry::new(header.clone(), b"hello".to_vec());
+        assert!(entry.data.is_empty());
+        assert_eq!(entry.header.size, 0);
+
+        // data set directly is not written after a non-regular header
+        let mut link = TarEntry::symlink("link", "d/");
+        link.data = b"hello".to_vec().into();
+        let read: Vec<(String, u8)> = read_tar(&write_tar(&[entry, link]).unwrap())
+            .into_iter()
+            .map(|e| (e.header.name, e.header.typeflag))
+            .collect();
+        assert_eq!(read, vec![("d/".to_string(), TYPE_DIRECTORY), ("link".to_string(), TYPE_SYMLINK)]);
+    }
 }
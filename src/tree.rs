@@ -0,0 +1,333 @@
+//! Read-only directory tree view over the entries of a Tar archive
+//!
+//! Archives often contain `dir/file.txt` without a header for `dir/`.
+//! The tree view synthesises such implicit parent directories, so an
+//! archive can be browsed like a filesystem.
+//!
+//! # Usage
+//!
+//! ```rust
+//! use tar_light::Tar;
+//!
+//! let mut tar = Tar::new();
+//! tar.add_str_entry("src/main.rs", "fn main() {}");
+//! tar.add_str_entry("src/bin/tool.rs", "fn main() {}");
+//! tar.add_str_entry("README.md", "# readme");
+//!
+//! // List a directory
+//! for entry in tar.read_dir("src").unwrap() {
+//!     println!("{} (dir: {})", entry.name, entry.metadata.is_dir());
+//! }
+//!
+//! // Implicit directories have metadata too
+//! let meta = tar.metadata("src/bin").unwrap();
+//! assert!(meta.is_dir() && meta.implicit);
+//!
+//! // Visit everything depth-first
+//! let paths: Vec<String> = tar.walk().into_iter().map(|e| e.path).collect();
+//! assert_eq!(paths, vec!["README.md", "src", "src/bin", "src/bin/tool.rs", "src/main.rs"]);
+//! ```
+
+use crate::tar::{EntryKind, Tar, TarHeader};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+
+/// Metadata of a path in the tree view
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metadata {
+    pub kind: EntryKind,
+    pub size: u64,
+    pub mode: u32,
+    pub mtime: u64,
+    pub uid: u32,
+    pub gid: u32,
+    pub linkname: String,
+    /// True if the directory has no header of its own in the archive
+    pub implicit: bool,
+}
+
+impl Metadata {
+    fn from_header(header: &TarHeader) -> Self {
+        Self {
+            kind: header.kind(),
+            size: header.size,
+            mode: header.mode,
+            mtime: header.mtime,
+            uid: header.uid,
+            gid: header.gid,
+            linkname: header.linkname.clone(),
+            implicit: false,
+        }
+    }
+
+    fn implicit_dir() -> Self {
+        Self {
+            kind: EntryKind::Directory,
+            size: 0,
+            mode: 0o755,
+            mtime: 0,
+            uid: 0,
+            gid: 0,
+            linkname: String::new(),
+            implicit: true,
+        }
+    }
+
+    /// Returns true if the path is a directory
+    pub fn is_dir(&self) -> bool {
+        self.kind == EntryKind::Directory
+    }
+
+    /// Returns true if the path is a regular file
+    pub fn is_file(&self) -> bool {
+        self.kind == EntryKind::File
+    }
+
+    /// Returns true if the path is a symbolic link
+    pub fn is_symlink(&self) -> bool {
+        self.kind == EntryKind::Symlink
+    }
+}
+
+/// An entry returned by `read_dir` and `walk`
+#[derive(Debug, Clone, PartialEq)]
+pub struct DirEntry {
+    /// Last path component
+    pub name: String,
+    /// Normalized path from the archive root, without trailing '/'
+    pub path: String,
+    pub metadata: Metadata,
+}
+
+/// Directory tree built from the entries of a Tar archive
+///
+/// A tree built with `new` is a snapshot; `Tar::tree` keeps one that is
+/// rebuilt after the archive is modified.
+#[derive(Debug, Clone, Default)]
+pub struct TarTree {
+    nodes: BTreeMap<String, Metadata>,
+    children: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl TarTree {
+    /// Build the tree view of a Tar archive
+    /// When a name occurs several times, the last occurrence is used
+    pub fn new(tar: &Tar) -> Self {
+        let mut tree = Self::default();
+        tree.children.insert(String::new(), BTreeSet::new());
+        for entry in &tar.entries {
+            let path = normalize_path(&entry.header.name);
+            if path.is_empty() {
+                continue;
+            }
+            tree.add_parents(&path);
+            let meta = Metadata::from_header(&entry.header);
+            if meta.is_dir() {
+                tree.children.entry(path.clone()).or_default();
+            }
+            tree.nodes.insert(path, meta);
+        }
+        tree
+    }
+
+    /// Register every ancestor of `path` as a directory
+    fn add_parents(&mut self, path: &str) {
+        let mut child = path;
+        while let Some(parent) = parent_of(child) {
+            let name = &child[child.rfind('/').map(|i| i + 1).unwrap_or(0)..];
+            let siblings = self.children.entry(parent.to_string()).or_default();
+            let known = !siblings.insert(name.to_string());
+            if !parent.is_empty() {
+                self.nodes.entry(parent.to_string()).or_insert_with(Metadata::implicit_dir);
+            }
+            if known {
+                break; // the rest of the chain is already registered
+            }
+            child = parent;
+        }
+    }
+
+    /// Get the metadata of a path
+    /// The root ("", "." or "/") is reported as an implicit directory
+    pub fn metadata(&self, path: &str) -> Option<Metadata> {
+        let path = normalize_path(path);
+        if path.is_empty() {
+            return Some(Metadata::implicit_dir());
+        }
+        self.nodes.get(&path).cloned()
+    }
+
+    /// List the direct children of a directory, sorted by name
+    pub fn read_dir(&self, path: &str) -> Result<Vec<DirEntry>, io::Error> {
+        let path = normalize_path(path);
+        match self.metadata(&path) {
+            None => {
+                return Err(io::Error::new(io::ErrorKind::NotFound, format!("not found: {}", path)));
+            }
+            Some(meta) if !meta.is_dir() => {
+                return Err(io::Error::new(io::ErrorKind::NotADirectory, format!("not a directory: {}", path)));
+            }
+            Some(_) => {}
+        }
+        let names = match self.children.get(&path) {
+            Some(names) => names,
+            None => return Ok(Vec::new()),
+        };
+        Ok(names
+            .iter()
+            .map(|name| {
+                let child = join_path(&path, name);
+                DirEntry {
+                    name: name.clone(),
+                    metadata: self.nodes[&child].clone(),
+                    path: child,
+                }
+            })
+            .collect())
+    }
+
+    /// Visit every path depth-first, children sorted by name
+    pub fn walk(&self) -> Vec<DirEntry> {
+        let mut result = Vec::new();
+        self.walk_dir("", &mut result);
+        result
+    }
+
+    fn walk_dir(&self, path: &str, result: &mut Vec<DirEntry>) {
+        let Some(names) = self.children.get(path) else {
+            return;
+        };
+        for name in names {
+            let child = join_path(path, name);
+            let metadata = self.nodes[&child].clone();
+            result.push(DirEntry { name: name.clone(), path: child.clone(), metadata });
+            self.walk_dir(&child, result);
+        }
+    }
+}
+
+impl Tar {
+    /// Directory tree view of the archive
+    /// The tree is built on first use and kept until the archive is modified
+    pub fn tree(&self) -> &TarTree {
+        self.tree.get_or_init(|| TarTree::new(self))
+    }
+
+    /// List the direct children of a directory in the archive
+    pub fn read_dir(&self, path: &str) -> Result<Vec<DirEntry>, io::Error> {
+        self.tree().read_dir(path)
+    }
+
+    /// Get the metadata of a path in the archive, including implicit directories
+    pub fn metadata(&self, path: &str) -> Option<Metadata> {
+        self.tree().metadata(path)
+    }
+
+    /// Visit every path in the archive depth-first
+    pub fn walk(&self) -> Vec<DirEntry> {
+        self.tree().walk()
+    }
+}
+
+/// Normalize an archive path: drop leading "/" and "./", empty and "." components
+/// and the trailing '/'
+fn normalize_path(path: &str) -> String {
+    path.split('/')
+        .filter(|c| !c.is_empty() && *c != ".")
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn parent_of(path: &str) -> Option<&str> {
+    if path.is_empty() {
+        return None;
+    }
+    Some(path.rfind('/').map(|i| &path[..i]).unwrap_or(""))
+}
+
+fn join_path(dir: &str, name: &str) -> String {
+    if dir.is_empty() { name.to_string() } else { format!("{}/{}", dir, name) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tar::TarEntry;
+
+    fn sample() -> Tar {
+        let mut tar = Tar::new();
+        tar.add_entry(TarEntry::directory("docs").with_mode(0o700));
+        tar.add_str_entry("docs/guide.md", "guide");
+        tar.add_str_entry("./src/lib.rs", "lib");
+        tar.add_str_entry("src/bin/tool.rs", "tool");
+        tar.add_entry(TarEntry::symlink("latest", "docs/guide.md"));
+        tar
+    }
+
+    #[test]
+    fn read_dir_test() {
+        let tar = sample();
+        let root: Vec<String> = tar.read_dir("").unwrap().into_iter().map(|e| e.name).collect();
+        assert_eq!(root, vec!["docs", "latest", "src"]);
+
+        let src = tar.read_dir("/src/").unwrap();
+        assert_eq!(src.len(), 2);
+        assert_eq!(src[0].path, "src/bin");
+        assert!(src[0].metadata.is_dir() && src[0].metadata.implicit);
+        assert_eq!(src[1].path, "src/lib.rs");
+        assert_eq!(src[1].metadata.size, 3);
+
+        assert_eq!(tar.read_dir("missing").unwrap_err().kind(), io::ErrorKind::NotFound);
+        assert_eq!(tar.read_dir("src/lib.rs").unwrap_err().kind(), io::ErrorKind::NotADirectory);
+    }
+
+    #[test]
+    fn metadata_test() {
+        let tar = sample();
+        let docs = tar.metadata("docs").unwrap();
+        assert!(docs.is_dir());
+        assert!(!docs.implicit);
+        assert_eq!(docs.mode, 0o700);
+
+        let link = tar.metadata("latest").unwrap();
+        assert!(link.is_symlink());
+        assert_eq!(link.linkname, "docs/guide.md");
+
+        assert!(tar.metadata(".").unwrap().is_dir());
+        assert!(tar.metadata("src/bin/tool.rs").unwrap().is_file());
+        assert!(tar.metadata("src/nope").is_none());
+    }
+
+    #[test]
+    fn walk_test() {
+        let tar = sample();
+        let paths: Vec<String> = tar.walk().into_iter().map(|e| e.path).collect();
+        assert_eq!(
+            paths,
+            vec!["docs", "docs/guide.md", "latest", "src", "src/bin", "src/bin/tool.rs", "src/lib.rs"]
+        );
+        assert!(Tar::new().walk().is_empty());
+    }
+
+    #[test]
+    fn tree_cache_test() {
+        let mut tar = sample();
+        // the tree is built once and shared by the lookups
+        assert!(std::ptr::eq(tar.tree(), tar.tree()));
+        assert_eq!(tar.read_dir("docs").unwrap().len(), 1);
+
+        // every kind of change is visible in the next lookup
+        tar.add_str_entry("docs/api.md", "api");
+        assert_eq!(tar.read_dir("docs").unwrap().len(), 2);
+        tar.find_entry_mut("docs/api.md").unwrap().header.name = "docs/ref.md".to_string();
+        assert!(tar.metadata("docs/ref.md").is_some());
+        assert!(tar.find_entry("docs/ref.md").is_some());
+        assert!(tar.find_entry("docs/api.md").is_none());
+        assert_eq!(tar.get_str("docs/ref.md").as_deref(), Some("api"));
+        tar.remove_entry("docs/guide.md");
+        assert_eq!(tar.read_dir("docs").unwrap()[0].name, "ref.md");
+        tar.entries.clear();
+        tar.reindex();
+        assert!(tar.walk().is_empty());
+    }
+}
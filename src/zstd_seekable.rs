@@ -0,0 +1,292 @@
+//! Seekable Zstandard archives
+//!
+//! The seekable format splits the compressed stream into independent zstd
+//! frames and appends a seek table in a skippable frame, so any byte range
+//! can be decompressed without reading the frames before it. tar_light
+//! starts a new frame at every entry, which makes single entries cheap to
+//! extract. Regular zstd decoders ignore the seek table and read the
+//! archive as usual.
+//!
+//! # Usage
+//!
+//! ```rust
+//! use tar_light::{CompressOptions, SeekableZstd, Tar};
+//!
+//! let mut tar = Tar::new();
+//! tar.add_str_entry("a.txt", "alpha");
+//! tar.add_str_entry("b.txt", "beta");
+//! let mut options = CompressOptions::default();
+//! options.zstd.seekable = true;
+//! tar.save_with("seekable_doc.tar.zst", &options).unwrap();
+//!
+//! let archive = SeekableZstd::open("seekable_doc.tar.zst").unwrap();
+//! let entry = archive.read_entry("b.txt").unwrap().unwrap();
+//! assert_eq!(entry.data, b"beta");
+//! # std::fs::remove_file("seekable_doc.tar.zst").unwrap();
+//! ```
+
+use crate::compression::{self, CompressOptions};
+use crate::random_access::{self, CountingWriter, Frame};
+use crate::tar::{TarEntry, TarHeader};
+use std::fs::File;
+use std::io::{self, Write};
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+
+/// Magic number of the skippable frame holding the seek table
+const SKIPPABLE_MAGIC: u32 = 0x184D_2A5E;
+/// Magic number at the very end of a seekable archive
+const SEEKABLE_MAGIC: u32 = 0x8F92_EAB1;
+/// Size of the seek table footer: frame count, descriptor and magic
+const FOOTER_SIZE: u64 = 9;
+/// Frames larger than this are split, so sizes fit the 32-bit seek table
+const MAX_FRAME_SIZE: u64 = 1 << 30;
+
+/// Writes independent zstd frames followed by a seek table
+pub(crate) struct SeekableEncoder<W: Write> {
+    options: CompressOptions,
+    writer: Option<CountingWriter<W>>,
+    frame: Option<zstd::stream::write::Encoder<'static, CountingWriter<W>>>,
+    frame_start: u64,
+    frame_size: u64,
+    frames: Vec<(u32, u32)>,
+}
+
+impl<W: Write> SeekableEncoder<W> {
+    pub(crate) fn new(writer: W, options: &CompressOptions) -> Self {
+        Self {
+            options: options.clone(),
+            writer: Some(CountingWriter::new(writer)),
+            frame: None,
+            frame_start: 0,
+            frame_size: 0,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Close the current frame; the next write starts a new one
+    pub(crate) fn end_frame(&mut self) -> Result<(), io::Error> {
+        let Some(frame) = self.frame.take() else {
+            return Ok(());
+        };
+        let writer = frame.finish()?;
+        let compressed = writer.count - self.frame_start;
+        let compressed = u32::try_from(compressed)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "zstd frame too large for the seek table"))?;
+        self.frames.push((compressed, self.frame_size as u32));
+        self.writer = Some(writer);
+        self.frame_size = 0;
+        Ok(())
+    }
+
+    /// Close the last frame, write the seek table and return the inner writer
+    pub(crate) fn finish(mut self) -> Result<W, io::Error> {
+        self.end_frame()?;
+        let mut writer = self.writer.take().expect("no open frame after end_frame");
+        let table_size = self.frames.len() * 8 + FOOTER_SIZE as usize;
+        let mut table = Vec::with_capacity(8 + table_size);
+        table.extend_from_slice(&SKIPPABLE_MAGIC.to_le_bytes());
+        table.extend_from_slice(&(table_size as u32).to_le_bytes());
+        for (compressed, size) in &self.frames {
+            table.extend_from_slice(&compressed.to_le_bytes());
+            table.extend_from_slice(&size.to_le_bytes());
+        }
+        table.extend_from_slice(&(self.frames.len() as u32).to_le_bytes());
+        table.push(0); // descriptor: no per-frame checksums
+        table.extend_from_slice(&SEEKABLE_MAGIC.to_le_bytes());
+        writer.write_all(&table)?;
+        Ok(writer.inner)
+    }
+}
+
+impl<W: Write> Write for SeekableEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.frame.is_none() {
+            let writer = self.writer.take().expect("no open frame without a writer");
+            self.frame_start = writer.count;
+            self.frame = Some(compression::zstd_encoder(writer, &self.options)?);
+        }
+        let room = (MAX_FRAME_SIZE - self.frame_size) as usize;
+        let n = self.frame.as_mut().expect("frame was just opened").write(&buf[..buf.len().min(room)])?;
+        self.frame_size += n as u64;
+        if self.frame_size == MAX_FRAME_SIZE {
+            self.end_frame()?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match (&mut self.frame, &mut self.writer) {
+            (Some(frame), _) => frame.flush(),
+            (None, Some(writer)) => writer.flush(),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// Random access to a seekable `.tar.zst` archive
+#[derive(Debug)]
+pub struct SeekableZstd {
+    file: File,
+    frames: Vec<Frame>,
+    options: CompressOptions,
+}
+
+impl SeekableZstd {
+    /// Open a seekable archive and read its seek table
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, io::Error> {
+        Self::open_with(path, &CompressOptions::default())
+    }
+
+    /// Open a seekable archive compressed with a dictionary
+    /// Only `options.zstd.dictionary` is used
+    pub fn open_with<P: AsRef<Path>>(path: P, options: &CompressOptions) -> Result<Self, io::Error> {
+        let file = File::open(path)?;
+        let frames = read_seek_table(&file)?;
+        Ok(Self { file, frames, options: options.clone() })
+    }
+
+    /// Number of frames in the archive
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Size of the decompressed tar stream
+    pub fn decompressed_len(&self) -> u64 {
+        random_access::decompressed_len(&self.frames)
+    }
+
+    /// Decompress `len` bytes at `offset` of the tar stream
+    /// Only the frames covering the range are read
+    pub fn read_at(&self, offset: u64, len: u64) -> Result<Vec<u8>, io::Error> {
+        random_access::read_range(&self.file, &self.frames, offset, len, |frame| {
+            Ok(Box::new(compression::zstd_decoder(frame, &self.options)?))
+        })
+    }
+
+    /// Headers of every entry in the archive, like `list`
+    pub fn headers(&self) -> Result<Vec<TarHeader>, io::Error> {
+        let scanned = random_access::scan(self.decompressed_len(), |offset, len| self.read_at(offset, len))?;
+        Ok(scanned.into_iter().map(|(h, _, _)| h).collect())
+    }
+
+    /// Extract one entry by name (last occurrence)
+    /// Frames are decoded only as far as needed, so finding the entry reads
+    /// just the header at the start of each frame before its data
+    pub fn read_entry(&self, name: &str) -> Result<Option<TarEntry>, io::Error> {
+        let scanned = random_access::scan(self.decompressed_len(), |offset, len| self.read_at(offset, len))?;
+        let Some((header, header_bytes, offset)) = scanned.into_iter().rev().find(|(h, _, _)| h.name == name) else {
+            return Ok(None);
+        };
+        let data = if header.is_file() { self.read_at(offset, header.size)? } else { Vec::new() };
+        Ok(Some(TarEntry { header, data: data.into(), header_bytes }))
+    }
+}
+
+/// Parse the seek table at the end of a seekable archive
+fn read_seek_table(file: &File) -> Result<Vec<Frame>, io::Error> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, format!("not a seekable zstd archive: {}", msg));
+    let len = file.metadata()?.len();
+    if len < 8 + FOOTER_SIZE {
+        return Err(invalid("file too short"));
+    }
+    let mut footer = [0u8; FOOTER_SIZE as usize];
+    file.read_exact_at(&mut footer, len - FOOTER_SIZE)?;
+    if u32::from_le_bytes(footer[5..9].try_into().unwrap()) != SEEKABLE_MAGIC {
+        return Err(invalid("missing seek table"));
+    }
+    let count = u32::from_le_bytes(footer[0..4].try_into().unwrap()) as u64;
+    if footer[4] & 0x7c != 0 {
+        return Err(invalid("reserved descriptor bits set"));
+    }
+    let entry_size = if footer[4] & 0x80 != 0 { 12 } else { 8 };
+    let table_size = count * entry_size + FOOTER_SIZE;
+    if table_size + 8 > len {
+        return Err(invalid("seek table larger than the file"));
+    }
+    let mut table = vec![0u8; (table_size + 8) as usize];
+    file.read_exact_at(&mut table, len - table_size - 8)?;
+    if u32::from_le_bytes(table[0..4].try_into().unwrap()) != SKIPPABLE_MAGIC
+        || u32::from_le_bytes(table[4..8].try_into().unwrap()) as u64 != table_size
+    {
+        return Err(invalid("bad seek table frame"));
+    }
+
+    let mut frames = Vec::with_capacity(count as usize);
+    let (mut compressed_offset, mut offset) = (0u64, 0u64);
+    for entry in table[8..].chunks_exact(entry_size as usize).take(count as usize) {
+        let compressed_size = u32::from_le_bytes(entry[0..4].try_into().unwrap()) as u64;
+        let size = u32::from_le_bytes(entry[4..8].try_into().unwrap()) as u64;
+        frames.push(Frame { compressed_offset, compressed_size, offset, size });
+        compressed_offset += compressed_size;
+        offset += size;
+    }
+    if compressed_offset > len - table_size - 8 {
+        return Err(invalid("frames overlap the seek table"));
+    }
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tar::Tar;
+
+    #[test]
+    fn seekable_round_trip_test() {
+        let path = "test_zstd_seekable.tar.zst";
+        let mut tar = Tar::new();
+        tar.add_str_entry("a.txt", "alpha");
+        tar.add_entry(TarEntry::file("big.bin", vec![9u8; 100_000]));
+        tar.add_str_entry("c.txt", "gamma");
+        let mut options = CompressOptions::default();
+        options.zstd.seekable = true;
+        tar.save_with(path, &options).unwrap();
+
+        // one frame per entry plus the end-of-archive marker
+        let archive = SeekableZstd::open(path).unwrap();
+        assert_eq!(archive.frame_count(), 4);
+        assert_eq!(archive.decompressed_len(), tar.to_bytes().unwrap().len() as u64);
+        let names: Vec<String> = archive.headers().unwrap().into_iter().map(|h| h.name).collect();
+        assert_eq!(names, vec!["a.txt", "big.bin", "c.txt"]);
+        assert_eq!(archive.read_entry("c.txt").unwrap().unwrap().data, b"gamma");
+        assert_eq!(archive.read_entry("big.bin").unwrap().unwrap().data.len(), 100_000);
+        assert!(archive.read_entry("missing").unwrap().is_none());
+
+        // regular decoders skip the seek table
+        assert_eq!(Tar::open(path).unwrap().to_bytes().unwrap(), tar.to_bytes().unwrap());
+
+        // frames are decoded only as far as needed: headers stay readable
+        // when the end of a frame is damaged
+        let noise: Vec<u8> = (0..400_000u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8).collect();
+        let mut tar = Tar::new();
+        tar.add_entry(TarEntry::file("noise.bin", noise));
+        tar.add_str_entry("c.txt", "gamma");
+        tar.save_with(path, &options).unwrap();
+        let frame = SeekableZstd::open(path).unwrap().frames[0];
+        let mut data = std::fs::read(path).unwrap();
+        let end = (frame.compressed_offset + frame.compressed_size) as usize;
+        data[end - 1000..end].fill(0xff);
+        std::fs::write(path, &data).unwrap();
+        let archive = SeekableZstd::open(path).unwrap();
+        let names: Vec<String> = archive.headers().unwrap().into_iter().map(|h| h.name).collect();
+        assert_eq!(names, vec!["noise.bin", "c.txt"]);
+        assert_eq!(archive.read_entry("c.txt").unwrap().unwrap().data, b"gamma");
+        assert!(archive.read_entry("noise.bin").is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn seek_table_errors_test() {
+        let path = "test_zstd_not_seekable.tar.zst";
+        let mut tar = Tar::new();
+        tar.add_str_entry("a.txt", "alpha");
+        tar.save(path).unwrap();
+        let err = SeekableZstd::open(path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        std::fs::remove_file(path).unwrap();
+    }
+}